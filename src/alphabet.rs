@@ -0,0 +1,144 @@
+//! Symbol alphabets: what one step carries.
+//!
+//! A step erases the pixel at `x = symbol`. With `Alphabet::Byte` the symbol
+//! is the payload byte, so only columns 0..256 are ever touched. With
+//! `Alphabet::Nine` the payload is read MSB-first as a stream of 9-bit
+//! symbols, using columns 0..512 and cutting N to `ceil(8 * len / 9)`. The
+//! final symbol is zero-padded in its low bits; the pad length is recorded in
+//! the CVP2 header (FLAG_SYMBOLS_9).
+
+use std::ops::Range;
+
+use crate::{CanvasConfig, CvpError, Result};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Alphabet {
+    /// One payload byte per step.
+    #[default]
+    Byte,
+    /// 9-bit symbols; needs a canvas at least 512 wide.
+    Nine,
+}
+
+impl Alphabet {
+    pub fn bits(self) -> u32 {
+        match self {
+            Alphabet::Byte => 8,
+            Alphabet::Nine => 9,
+        }
+    }
+
+    /// `BadDims` unless every symbol has a column on `cfg`.
+    pub fn check(self, cfg: CanvasConfig) -> Result<()> {
+        if cfg.w() < 1 << self.bits() {
+            return Err(CvpError::BadDims { w: cfg.w(), h: cfg.h() });
+        }
+        Ok(())
+    }
+
+    /// Number of steps (symbols) for a payload of `len` bytes, and the
+    /// padding bits in the last symbol.
+    pub fn steps_for_len(self, len: u64) -> (u64, u8) {
+        let bits = len as u128 * 8;
+        let n = bits.div_ceil(self.bits() as u128);
+        (n as u64, (n * self.bits() as u128 - bits) as u8)
+    }
+
+    /// Payload length in bytes of `n` symbols ending in `pad_bits` of padding.
+    pub fn payload_len(self, n: u64, pad_bits: u8) -> u64 {
+        ((n as u128 * self.bits() as u128 - pad_bits as u128) / 8) as u64
+    }
+
+    /// 0-based symbol indices covering payload `bytes`, and how many leading
+    /// bits of the first symbol precede `bytes.start`.
+    pub(crate) fn symbols_for(self, bytes: Range<u64>) -> (Range<u64>, u32) {
+        let b = self.bits() as u128;
+        let (start, end) = (bytes.start as u128 * 8, bytes.end as u128 * 8);
+        let first = start / b;
+        (first as u64..end.div_ceil(b) as u64, (start - first * b) as u32)
+    }
+}
+
+/// Splits a byte stream into 9-bit symbols.
+#[derive(Clone, Debug, Default)]
+pub struct Packer {
+    acc: u32,
+    bits: u32,
+}
+
+impl Packer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feed one byte; returns the symbol it completes, if any.
+    #[inline]
+    pub fn push(&mut self, byte: u8) -> Option<u16> {
+        self.acc = (self.acc << 8) | byte as u32;
+        self.bits += 8;
+        if self.bits < 9 {
+            return None;
+        }
+        self.bits -= 9;
+        let sym = (self.acc >> self.bits) as u16;
+        self.acc &= (1 << self.bits) - 1;
+        Some(sym)
+    }
+
+    /// The zero-padded last symbol (if bits remain) and its padding length.
+    pub fn finish(self) -> (Option<u16>, u8) {
+        if self.bits == 0 {
+            return (None, 0);
+        }
+        let pad = 9 - self.bits;
+        (Some((self.acc << pad) as u16), pad as u8)
+    }
+
+    /// Whole-payload packing: the symbols and the padding length.
+    pub fn pack(payload: &[u8]) -> (Vec<u16>, u8) {
+        let mut packer = Packer::new();
+        let mut out = Vec::with_capacity(payload.len() * 8 / 9 + 1);
+        out.extend(payload.iter().filter_map(|&b| packer.push(b)));
+        let (last, pad) = packer.finish();
+        out.extend(last);
+        (out, pad)
+    }
+}
+
+/// Joins 9-bit symbols back into bytes. Bits that do not complete a byte
+/// (the padding) are dropped.
+#[derive(Clone, Debug, Default)]
+pub struct Unpacker {
+    acc: u32,
+    bits: u32,
+    skip: u32,
+}
+
+impl Unpacker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Discard the first `skip` bits, to start mid-symbol.
+    pub(crate) fn skipping(skip: u32) -> Self {
+        Self { skip, ..Self::default() }
+    }
+
+    #[inline]
+    pub fn push(&mut self, sym: u16, out: &mut Vec<u8>) {
+        let mut width = 9;
+        let mut sym = sym as u32 & 0x1ff;
+        if self.skip > 0 {
+            width -= self.skip;
+            sym &= (1 << width) - 1;
+            self.skip = 0;
+        }
+        self.acc = (self.acc << width) | sym;
+        self.bits += width;
+        while self.bits >= 8 {
+            self.bits -= 8;
+            out.push((self.acc >> self.bits) as u8);
+        }
+        self.acc &= (1 << self.bits) - 1;
+    }
+}
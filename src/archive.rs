@@ -0,0 +1,208 @@
+//! Multi-file archive: named entries stored back to back in one chain.
+//!
+//! ```text
+//! magic     4   b"CVPA"
+//! version   u8  format version (VERSION)
+//! reserved  u8  0
+//! count     u32 number of entries
+//! entries   count * (path_len u16, path UTF-8, size u64, mode u32, mtime i64)
+//! body      CVPC chain of every entry's bytes in entry order; absent when
+//!           all entries are empty
+//! ```
+//!
+//! Entry `i` is the payload range starting at the sum of the sizes before it,
+//! so extracting one entry only decodes the canvases that range touches, and
+//! adding entries appends canvases without re-encoding the existing ones.
+//! Paths are relative and `/`-separated with no empty, `.` or `..`
+//! components, and unique within the archive.
+
+use std::collections::HashSet;
+
+use crate::chain::{self, ChainOptions};
+use crate::sections::{malformed, Reader};
+use crate::{CvpError, DecodeOptions, Result};
+
+pub const MAGIC: &[u8; 4] = b"CVPA";
+pub const VERSION: u8 = 1;
+
+/// Stored metadata of one entry.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Entry {
+    pub path: String,
+    pub size: u64,
+    /// Unix permission bits.
+    pub mode: u32,
+    /// Modification time, seconds since the Unix epoch.
+    pub mtime: i64,
+}
+
+/// An entry to store; its size is the length of `data`.
+#[derive(Clone, Debug)]
+pub struct NewEntry<'a> {
+    pub path: String,
+    pub mode: u32,
+    pub mtime: i64,
+    pub data: &'a [u8],
+}
+
+/// Entry index and the chain holding their bytes.
+struct Index<'a> {
+    entries: Vec<Entry>,
+    body: &'a [u8],
+}
+
+/// Why `path` cannot name an entry, if it cannot.
+fn path_problem(path: &str) -> Option<&'static str> {
+    if path.is_empty() {
+        Some("empty path")
+    } else if path.starts_with('/') || path.contains('\\') || path.contains('\0') {
+        Some("must be relative and /-separated")
+    } else if path.split('/').any(|c| c.is_empty() || c == "." || c == "..") {
+        Some("empty, . or .. component")
+    } else {
+        None
+    }
+}
+
+fn bad_path(path: &str, reason: &'static str) -> CvpError {
+    CvpError::BadEntryPath { path: path.to_string(), reason }
+}
+
+fn read_index(raw: &[u8]) -> Result<Index<'_>> {
+    if raw.len() < 4 { return Err(CvpError::TooSmall { len: raw.len() }); }
+    if &raw[0..4] != MAGIC {
+        return Err(CvpError::BadMagic { found: raw[0..4].try_into().unwrap() });
+    }
+    let mut rd = Reader::new(raw, 4);
+    let version = rd.u8("version")?;
+    if version != VERSION { return Err(CvpError::UnsupportedVersion { version }); }
+    let at = rd.off;
+    let reserved = rd.u8("reserved")?;
+    if reserved != 0 { return Err(malformed(at, "reserved", reserved as u64, "must be 0")); }
+    let at = rd.off;
+    let count = rd.u32("count")?;
+    // every entry takes at least 2 + 1 + 8 + 4 + 8 bytes
+    if count as usize > rd.remaining() / 23 {
+        return Err(malformed(at, "count", count as u64, "entries exceed remaining bytes"));
+    }
+
+    let mut entries = Vec::with_capacity(count as usize);
+    let mut seen = HashSet::new();
+    let mut total = 0u64;
+    for _ in 0..count {
+        let path_len = rd.u16("path_len")?;
+        let at = rd.off;
+        let path = std::str::from_utf8(rd.bytes(path_len as usize, "path")?)
+            .map_err(|_| malformed(at, "path", path_len as u64, "not UTF-8"))?
+            .to_string();
+        if let Some(reason) = path_problem(&path) { return Err(bad_path(&path, reason)); }
+        let at = rd.off;
+        let size = rd.u64("size")?;
+        total = total.checked_add(size).ok_or(malformed(at, "size", size, "archive size overflows u64"))?;
+        let mode = rd.u32("mode")?;
+        let mtime = rd.u64("mtime")? as i64;
+        if !seen.insert(path.clone()) { return Err(bad_path(&path, "duplicate entry")); }
+        entries.push(Entry { path, size, mode, mtime });
+    }
+
+    let body_at = rd.off;
+    let body = &raw[body_at..];
+    let stored = if body.is_empty() { 0 } else { chain::links(body)?.iter().map(|l| l.payload_len).sum() };
+    if stored != total {
+        return Err(malformed(body_at, "body", stored, "payload length disagrees with entry sizes"));
+    }
+    Ok(Index { entries, body })
+}
+
+fn write_archive(entries: &[Entry], body: &[u8]) -> Result<Vec<u8>> {
+    let limit = |field, value: usize, max: u64| {
+        CvpError::FormatLimit { format: "archive", field, value: value as u64, max }
+    };
+    let count = u32::try_from(entries.len()).map_err(|_| limit("count", entries.len(), u32::MAX as u64))?;
+    let mut out = Vec::with_capacity(10 + entries.len() * 32 + body.len());
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(&[VERSION, 0]);
+    out.extend_from_slice(&count.to_le_bytes());
+    for e in entries {
+        let path_len =
+            u16::try_from(e.path.len()).map_err(|_| limit("path length", e.path.len(), u16::MAX as u64))?;
+        out.extend_from_slice(&path_len.to_le_bytes());
+        out.extend_from_slice(e.path.as_bytes());
+        out.extend_from_slice(&e.size.to_le_bytes());
+        out.extend_from_slice(&e.mode.to_le_bytes());
+        out.extend_from_slice(&e.mtime.to_le_bytes());
+    }
+    out.extend_from_slice(body);
+    Ok(out)
+}
+
+/// Build an archive of `files`, in order.
+pub fn create(files: &[NewEntry], opts: &ChainOptions) -> Result<Vec<u8>> {
+    add(&write_archive(&[], &[])?, files, opts)
+}
+
+/// Append `files` to an archive. Existing canvases are kept as they are; the
+/// new bytes go into canvases encoded with `opts`.
+pub fn add(raw: &[u8], files: &[NewEntry], opts: &ChainOptions) -> Result<Vec<u8>> {
+    let Index { mut entries, body } = read_index(raw)?;
+    let mut seen: HashSet<&str> = entries.iter().map(|e| e.path.as_str()).collect();
+    for f in files {
+        if let Some(reason) = path_problem(&f.path) { return Err(bad_path(&f.path, reason)); }
+        if !seen.insert(&f.path) { return Err(bad_path(&f.path, "duplicate entry")); }
+    }
+
+    let payload: Vec<u8> = files.iter().flat_map(|f| f.data.iter().copied()).collect();
+    let body = match (payload.is_empty(), body.is_empty()) {
+        (true, _) => body.to_vec(),
+        (false, true) => chain::encode_with(&payload, opts)?,
+        (false, false) => chain::append(body, &payload, opts)?,
+    };
+    entries.extend(files.iter().map(|f| Entry {
+        path: f.path.clone(),
+        size: f.data.len() as u64,
+        mode: f.mode,
+        mtime: f.mtime,
+    }));
+    write_archive(&entries, &body)
+}
+
+/// Entries in archive order, read from the index without decoding anything.
+pub fn list(raw: &[u8]) -> Result<Vec<Entry>> {
+    Ok(read_index(raw)?.entries)
+}
+
+/// Bytes of the entry named `path`, decoding and verifying only the canvases
+/// it spans.
+pub fn extract(raw: &[u8], path: &str, opts: &DecodeOptions) -> Result<Vec<u8>> {
+    let index = read_index(raw)?;
+    let mut offset = 0;
+    for e in &index.entries {
+        if e.path == path {
+            if e.size == 0 {
+                return Ok(Vec::new());
+            }
+            return chain::decode_range(index.body, offset..offset + e.size, opts);
+        }
+        offset += e.size;
+    }
+    Err(CvpError::EntryNotFound { path: path.to_string() })
+}
+
+/// The CVPC body of a validated archive; empty when every entry is.
+pub(crate) fn body(raw: &[u8]) -> Result<&[u8]> {
+    Ok(read_index(raw)?.body)
+}
+
+/// Every entry with its bytes; the whole body is decoded and verified once.
+pub fn extract_all(raw: &[u8], opts: &DecodeOptions) -> Result<Vec<(Entry, Vec<u8>)>> {
+    let index = read_index(raw)?;
+    let payload = if index.body.is_empty() { Vec::new() } else { chain::decode_with(index.body, opts)? };
+    let mut rest = payload.as_slice();
+    let mut out = Vec::with_capacity(index.entries.len());
+    for e in index.entries {
+        let (data, tail) = rest.split_at(e.size as usize);
+        rest = tail;
+        out.push((e, data.to_vec()));
+    }
+    Ok(out)
+}
@@ -0,0 +1,107 @@
+//! Capacity planning: per-pixel lane load computed from the step mapping
+//! alone, so an encode that would run a lane past RG_LIMIT can be predicted
+//! without building the canvas or the A bitset. Only touched (pixel, lane)
+//! pairs are kept, so the cost follows the payload, not the canvas.
+//!
+//! The load of a (pixel, lane) is the sum of `k` over the steps it absorbs.
+//! Under the FULL baseline the lane underflows once its load exceeds
+//! RG_LIMIT; under ZERO it overflows at the same point, so the report holds
+//! for either.
+
+use std::collections::HashMap;
+
+use crate::alphabet::Packer;
+use crate::{Alphabet, CanvasConfig, CvpError, EncodeOptions, Lanes, Result, RG_LIMIT_EXACT};
+
+/// Load of one (pixel, lane).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PixelLoad {
+    pub pidx: u32,
+    /// 0=R, 1=G, 2=B, 3=alpha
+    pub lane: u8,
+    pub load: u128,
+}
+
+#[derive(Clone, Debug)]
+pub struct CapacityReport {
+    pub cfg: CanvasConfig,
+    pub lanes: Lanes,
+    /// Steps the payload needs.
+    pub n: u64,
+    /// Largest load of any (pixel, lane).
+    pub max_load: u128,
+    /// Longest prefix, in steps, whose loads all stay within RG_LIMIT;
+    /// equal to `n` if the whole payload fits.
+    pub max_safe_n: u64,
+    /// Load of every touched (pixel, lane), keyed by its index in the RG
+    /// planes (lane * pixels + pidx).
+    loads: HashMap<usize, u128>,
+}
+
+impl CapacityReport {
+    /// True if the payload encodes without any lane passing RG_LIMIT.
+    pub fn fits(&self) -> bool {
+        self.max_safe_n == self.n
+    }
+
+    /// RG_LIMIT minus the largest load, or `None` if some lane overflows.
+    /// Under the FULL baseline this is the smallest lane value after encode.
+    pub fn headroom(&self) -> Option<u64> {
+        (RG_LIMIT_EXACT as u128).checked_sub(self.max_load).map(|h| h as u64)
+    }
+
+    /// The `count` most loaded (pixel, lane) pairs, heaviest first; ties in
+    /// lane then pidx order.
+    pub fn worst(&self, count: usize) -> Vec<PixelLoad> {
+        let pixels = self.cfg.pixels();
+        let mut out: Vec<PixelLoad> = self
+            .loads
+            .iter()
+            .filter(|&(_, &load)| load > 0)
+            .map(|(&i, &load)| PixelLoad { pidx: (i % pixels) as u32, lane: (i / pixels) as u8, load })
+            .collect();
+        out.sort_by(|a, b| b.load.cmp(&a.load).then((a.lane, a.pidx).cmp(&(b.lane, b.pidx))));
+        out.truncate(count);
+        out
+    }
+}
+
+/// Capacity of `payload` under the default encoding profile.
+pub fn capacity_report(payload: &[u8]) -> Result<CapacityReport> {
+    capacity_report_with(payload, &EncodeOptions::default())
+}
+
+/// Capacity of `payload` under `opts` (geometry, schedule, lanes and
+/// alphabet; the RG/A layout flags do not affect capacity).
+pub fn capacity_report_with(payload: &[u8], opts: &EncodeOptions) -> Result<CapacityReport> {
+    if payload.is_empty() { return Err(CvpError::EmptyPayload); }
+    let profile = opts.profile;
+    opts.alphabet.check(profile.cfg)?;
+
+    let cfg = profile.cfg;
+    let pixels = cfg.pixels();
+    let mut loads = HashMap::new();
+    let mut safe = None;
+    let mut step = 0u64;
+    let mut visit = |symbol: u16| {
+        step += 1;
+        let pidx = cfg.symbol_pidx(symbol, step) as usize;
+        let (lane, k) = profile.lane_k(step);
+        let load = loads.entry(lane as usize * pixels + pidx).or_insert(0u128);
+        *load += k as u128;
+        if safe.is_none() && *load > RG_LIMIT_EXACT as u128 {
+            safe = Some(step - 1);
+        }
+    };
+    match opts.alphabet {
+        Alphabet::Byte => payload.iter().for_each(|&b| visit(b as u16)),
+        Alphabet::Nine => {
+            let mut packer = Packer::new();
+            payload.iter().filter_map(|&b| packer.push(b)).for_each(&mut visit);
+            packer.finish().0.into_iter().for_each(&mut visit);
+        }
+    }
+
+    let max_load = loads.values().copied().max().unwrap_or(0);
+    Ok(CapacityReport { cfg, lanes: profile.lanes, n: step, max_load, max_safe_n: safe.unwrap_or(step), loads })
+}
@@ -0,0 +1,254 @@
+//! Chained container: a payload split across several canvases.
+//!
+//! ```text
+//! magic     4   b"CVPC"
+//! version   u8  format version (VERSION)
+//! reserved  u8  0
+//! count     u32 number of canvases, at least 1
+//! digest    32  SHA-256 over (payload_len u64, trailer SHA-256) of every
+//!               canvas, in manifest order
+//! manifest  count * (payload_len u64, canvas_len u64), in payload order
+//! canvases  count complete CVP2 files with integrity trailers, back to back,
+//!           no trailing bytes
+//! ```
+//!
+//! Steps restart at 1 on every canvas, so `k` never grows past what one
+//! canvas can absorb. The encoder cuts a segment wherever `capacity_report`
+//! predicts a lane would pass RG_LIMIT, or at `ChainOptions::max_steps`.
+//!
+//! The digest binds the canvases' order and content: `links` checks it
+//! against the trailers (walking section headers only), and decoding a canvas
+//! checks its trailer against the payload it peels to. A full decode thus
+//! verifies the whole payload; `decode_range` verifies the order of every
+//! canvas but the content only of those it touches.
+
+use std::ops::Range;
+
+use crate::sections::{malformed, Reader};
+use crate::{
+    capacity_report_with, cvp2, decode_range_with, decode_with as decode_canvas, encode_with as encode_canvas,
+    integrity, CanvasView, CvpError, DecodeOptions, EncodeOptions, Result,
+};
+
+pub const MAGIC: &[u8; 4] = b"CVPC";
+pub const VERSION: u8 = 1;
+/// magic + version + reserved + count + digest
+pub const HEADER_LEN: usize = 42;
+/// payload_len u64 + canvas_len u64
+pub const MANIFEST_ENTRY_LEN: usize = 16;
+/// Default step budget per canvas; bounds a decoder's step index at 64 MiB.
+pub const DEFAULT_MAX_STEPS: u64 = 1 << 24;
+
+#[derive(Clone, Debug)]
+pub struct ChainOptions {
+    /// Profile every canvas is encoded with.
+    pub encode: EncodeOptions,
+    /// Most steps a single canvas may take (at least 1).
+    pub max_steps: u64,
+}
+
+impl Default for ChainOptions {
+    fn default() -> Self {
+        Self { encode: EncodeOptions::default(), max_steps: DEFAULT_MAX_STEPS }
+    }
+}
+
+/// One canvas of a chain; `offset` is the absolute offset of `raw` in the file.
+#[derive(Clone, Copy, Debug)]
+pub struct Link<'a> {
+    /// Payload bytes this canvas decodes to.
+    pub payload_len: u64,
+    pub offset: usize,
+    pub raw: &'a [u8],
+    /// Offset of this link's manifest entry.
+    pub(crate) entry_offset: usize,
+}
+
+/// Encode `payload` as a chain with the default options.
+pub fn encode(payload: &[u8]) -> Result<Vec<u8>> {
+    encode_with(payload, &ChainOptions::default())
+}
+
+/// Split `payload` into the longest segments that fit, encode each as its own
+/// canvas and write the chain. A payload that fits one canvas still produces a
+/// chain of one.
+pub fn encode_with(payload: &[u8], opts: &ChainOptions) -> Result<Vec<u8>> {
+    let canvases = encode_canvases(payload, opts)?;
+    write_chain(canvases.iter().map(|(len, raw)| (*len, raw.as_slice())))
+}
+
+/// Extend an existing chain with canvases for `payload`; the canvases already
+/// in `raw` are copied as they are, not re-encoded.
+pub fn append(raw: &[u8], payload: &[u8], opts: &ChainOptions) -> Result<Vec<u8>> {
+    let links = links(raw)?;
+    let canvases = encode_canvases(payload, opts)?;
+    let old = links.iter().map(|l| (l.payload_len, l.raw));
+    write_chain(old.chain(canvases.iter().map(|(len, raw)| (*len, raw.as_slice()))))
+}
+
+/// Encode `payload` as `(payload_len, canvas)` segments.
+fn encode_canvases(payload: &[u8], opts: &ChainOptions) -> Result<Vec<(u64, Vec<u8>)>> {
+    if payload.is_empty() { return Err(CvpError::EmptyPayload); }
+    let alphabet = opts.encode.alphabet;
+    let budget = alphabet.payload_len(opts.max_steps.max(1), 0).max(1);
+
+    let mut canvases = Vec::new();
+    let mut rest = payload;
+    while !rest.is_empty() {
+        let mut len = rest.len().min(usize::try_from(budget).unwrap_or(usize::MAX));
+        loop {
+            let report = capacity_report_with(&rest[..len], &opts.encode)?;
+            if report.fits() || len == 1 {
+                break;
+            }
+            len = (alphabet.payload_len(report.max_safe_n, 0) as usize).clamp(1, len - 1);
+        }
+        canvases.push((len as u64, encode_canvas(&rest[..len], &opts.encode)?));
+        rest = &rest[len..];
+    }
+    Ok(canvases)
+}
+
+fn write_chain<'a>(canvases: impl Iterator<Item = (u64, &'a [u8])> + Clone) -> Result<Vec<u8>> {
+    let count = canvases.clone().count();
+    let count = u32::try_from(count).map_err(|_| CvpError::FormatLimit {
+        format: "chain",
+        field: "count",
+        value: count as u64,
+        max: u32::MAX as u64,
+    })?;
+    let body: usize = canvases.clone().map(|(_, raw)| raw.len()).sum();
+    let mut out = Vec::with_capacity(HEADER_LEN + count as usize * MANIFEST_ENTRY_LEN + body);
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(&[VERSION, 0]);
+    out.extend_from_slice(&count.to_le_bytes());
+    out.extend_from_slice(&digest(canvases.clone())?);
+    for (payload_len, raw) in canvases.clone() {
+        out.extend_from_slice(&payload_len.to_le_bytes());
+        out.extend_from_slice(&(raw.len() as u64).to_le_bytes());
+    }
+    for (_, raw) in canvases {
+        out.extend_from_slice(raw);
+    }
+    Ok(out)
+}
+
+/// Chain digest of `(payload_len, canvas)` pairs; `Chain` if a canvas has no
+/// integrity trailer.
+fn digest<'a>(canvases: impl Iterator<Item = (u64, &'a [u8])>) -> Result<[u8; 32]> {
+    let mut buf = Vec::new();
+    for (index, (payload_len, raw)) in canvases.enumerate() {
+        let sha256 = trailer_sha256(raw).map_err(|e| CvpError::Chain { index: index as u32, error: Box::new(e) })?;
+        buf.extend_from_slice(&payload_len.to_le_bytes());
+        buf.extend_from_slice(&sha256);
+    }
+    Ok(integrity::sha256(&buf))
+}
+
+/// SHA-256 from a canvas's integrity trailer, without decoding the canvas.
+fn trailer_sha256(raw: &[u8]) -> Result<[u8; 32]> {
+    let missing = CvpError::MissingSection { tag: cvp2::SEC_INTEGRITY };
+    if raw.starts_with(crate::MAGIC) { return Err(missing); }
+    let (_, start) = cvp2::read_header(raw)?;
+    let sec = cvp2::sections(raw, start)?.into_iter().find(|s| s.tag == cvp2::SEC_INTEGRITY).ok_or(missing)?;
+    let mut rd = Reader::new(&raw[..sec.offset + sec.body.len()], sec.offset + 4);
+    Ok(rd.bytes(32, "sha256")?.try_into().unwrap())
+}
+
+/// Parse the chain header and manifest, check the chain digest and borrow
+/// each canvas. The canvases are not decoded here.
+pub fn links(raw: &[u8]) -> Result<Vec<Link<'_>>> {
+    if raw.len() < 4 { return Err(CvpError::TooSmall { len: raw.len() }); }
+    if &raw[0..4] != MAGIC {
+        return Err(CvpError::BadMagic { found: raw[0..4].try_into().unwrap() });
+    }
+    let mut rd = Reader::new(raw, 4);
+    let version = rd.u8("version")?;
+    if version != VERSION { return Err(CvpError::UnsupportedVersion { version }); }
+    let at = rd.off;
+    let reserved = rd.u8("reserved")?;
+    if reserved != 0 { return Err(malformed(at, "reserved", reserved as u64, "must be 0")); }
+    let at = rd.off;
+    let count = rd.u32("count")?;
+    if count == 0 { return Err(malformed(at, "count", 0, "empty chain")); }
+    let stored: [u8; 32] = rd.bytes(32, "digest")?.try_into().unwrap();
+    if count as usize > rd.remaining() / MANIFEST_ENTRY_LEN {
+        return Err(malformed(at, "count", count as u64, "manifest exceeds remaining bytes"));
+    }
+
+    let mut entries = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        let entry_offset = rd.off;
+        let payload_len = rd.u64("payload_len")?;
+        if payload_len == 0 { return Err(malformed(entry_offset, "payload_len", 0, "empty canvas")); }
+        let canvas_len = rd.u64("canvas_len")?;
+        entries.push((entry_offset, payload_len, canvas_len));
+    }
+
+    let mut out = Vec::with_capacity(entries.len());
+    for (entry_offset, payload_len, canvas_len) in entries {
+        if canvas_len > rd.remaining() as u64 {
+            return Err(malformed(entry_offset + 8, "canvas_len", canvas_len, "exceeds remaining bytes"));
+        }
+        let offset = rd.off;
+        let raw = rd.bytes(canvas_len as usize, "canvas")?;
+        out.push(Link { payload_len, offset, raw, entry_offset });
+    }
+    if rd.remaining() != 0 {
+        return Err(malformed(rd.off, "trailing bytes", rd.remaining() as u64, "after last canvas"));
+    }
+    if digest(out.iter().map(|l| (l.payload_len, l.raw)))? != stored {
+        return Err(CvpError::ChainDigestMismatch);
+    }
+    Ok(out)
+}
+
+pub fn decode(raw: &[u8]) -> Result<Vec<u8>> {
+    decode_with(raw, &DecodeOptions::default())
+}
+
+/// Decode and verify every canvas in order; each must decode to exactly its
+/// manifest length. Canvas errors are reported as `CvpError::Chain`.
+pub fn decode_with(raw: &[u8], opts: &DecodeOptions) -> Result<Vec<u8>> {
+    let links = links(raw)?;
+    let mut out = Vec::new();
+    for (index, link) in links.iter().enumerate() {
+        let payload = decode_canvas(link.raw, opts)
+            .map_err(|e| CvpError::Chain { index: index as u32, error: Box::new(e) })?;
+        if payload.len() as u64 != link.payload_len {
+            return Err(malformed(link.entry_offset, "payload_len", link.payload_len, "disagrees with canvas"));
+        }
+        out.extend_from_slice(&payload);
+    }
+    Ok(out)
+}
+
+/// Payload bytes `range` of the whole chain. Only the canvases overlapping
+/// the range are decoded, and each of those is fully verified before its
+/// bytes are returned. The chain digest still pins every canvas's place and
+/// trailer, but the other canvases' contents are not checked against them.
+pub fn decode_range(raw: &[u8], range: Range<u64>, opts: &DecodeOptions) -> Result<Vec<u8>> {
+    let links = links(raw)?;
+    let len = links.iter().map(|l| l.payload_len).fold(0u64, u64::saturating_add);
+    let Range { start, end } = range;
+    if start > end || end > len {
+        return Err(CvpError::RangeOutOfBounds { start, end, len });
+    }
+
+    let mut out = Vec::with_capacity((end - start) as usize);
+    let mut at = 0u64;
+    for (index, link) in links.iter().enumerate() {
+        let (first, last) = (at, at.saturating_add(link.payload_len));
+        at = last;
+        if last <= start || first >= end {
+            continue;
+        }
+        let sub = start.max(first) - first..end.min(last) - first;
+        let wrap = |e| CvpError::Chain { index: index as u32, error: Box::new(e) };
+        if CanvasView::with_options(link.raw, opts).map_err(wrap)?.header().payload_len() != link.payload_len {
+            return Err(malformed(link.entry_offset, "payload_len", link.payload_len, "disagrees with canvas"));
+        }
+        out.extend_from_slice(&decode_range_with(link.raw, sub, Some(opts)).map_err(wrap)?);
+    }
+    Ok(out)
+}
@@ -0,0 +1,114 @@
+//! Canvas geometry: power-of-two width and height, and the `Profile` that
+//! pairs it with a baseline, lane schedule and lane count.
+//!
+//! A payload byte `b` at step `s` lands on `x = b`, `y = s & time_mask`, so
+//! the width must hold every byte value and the height sets how many steps
+//! pass before the time axis wraps. Smaller canvases make smaller files (the
+//! RG planes are `2 * w * h` u64s) at the cost of more steps per pixel.
+
+use crate::{Baseline, CvpError, Lanes, Result, Schedule, H, W};
+
+/// Narrowest canvas: one column per byte value.
+pub const MIN_W: u32 = 256;
+/// Largest canvas the format allows (16M pixels, 256 MiB of planes); readers
+/// further cap what they allocate with `DecodeOptions::max_pixels`.
+pub const MAX_PIXELS: usize = 1 << 24;
+/// Default decode-side limit (`DecodeOptions::max_pixels`): 1M pixels, at most
+/// 32 MiB of planes with four lanes. An RG-elided file is tiny whatever its
+/// canvas, so the canvas size alone must not decide what a reader allocates.
+pub const DEFAULT_MAX_DECODE_PIXELS: u64 = 1 << 20;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct CanvasConfig {
+    w: u32,
+    h: u32,
+}
+
+impl Default for CanvasConfig {
+    fn default() -> Self {
+        Self { w: W, h: H }
+    }
+}
+
+impl CanvasConfig {
+    /// Both sides must be powers of two, `w >= MIN_W` and `w * h <= MAX_PIXELS`.
+    pub fn new(w: u32, h: u32) -> Result<Self> {
+        let pixels = w as u64 * h as u64;
+        if !w.is_power_of_two() || !h.is_power_of_two() || w < MIN_W || pixels > MAX_PIXELS as u64 {
+            return Err(CvpError::BadDims { w, h });
+        }
+        Ok(Self { w, h })
+    }
+
+    pub fn w(&self) -> u32 {
+        self.w
+    }
+
+    pub fn h(&self) -> u32 {
+        self.h
+    }
+
+    pub fn pixels(&self) -> usize {
+        self.w as usize * self.h as usize
+    }
+
+    /// Mask applied to the step to get the row (`H - 1`).
+    #[inline]
+    pub fn time_mask(&self) -> u64 {
+        self.h as u64 - 1
+    }
+
+    #[inline]
+    pub fn pidx_of(&self, x: u32, y: u32) -> u32 {
+        (y << self.w.trailing_zeros()) + x
+    }
+
+    /// Pixel a byte erases at `step`.
+    #[inline]
+    pub fn pidx_at(&self, byte: u8, step: u64) -> u32 {
+        self.symbol_pidx(byte as u16, step)
+    }
+
+    /// Pixel a symbol of any `Alphabet` erases at `step`; `symbol < w`.
+    #[inline]
+    pub fn symbol_pidx(&self, symbol: u16, step: u64) -> u32 {
+        self.pidx_of(symbol as u32, (step & self.time_mask()) as u32)
+    }
+
+    /// Column and row of `pidx`.
+    #[inline]
+    pub fn xy(&self, pidx: u32) -> (u32, u32) {
+        (pidx & (self.w - 1), pidx >> self.w.trailing_zeros())
+    }
+
+    /// Payload byte a pixel decodes to.
+    #[inline]
+    pub fn byte_of(&self, pidx: u32) -> u8 {
+        self.xy(pidx).0 as u8
+    }
+
+    /// Symbol a pixel decodes to (its column).
+    #[inline]
+    pub fn symbol_of(&self, pidx: u32) -> u16 {
+        self.xy(pidx).0 as u16
+    }
+}
+
+/// Everything that decides where a step lands and how it moves its lane:
+/// geometry, baseline, schedule and lane count. Carried by `RGCanvas`,
+/// `cvp2::Header` and `EncodeOptions`; the default is the CVP1 profile.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Profile {
+    pub cfg: CanvasConfig,
+    pub baseline: Baseline,
+    pub schedule: Schedule,
+    pub lanes: Lanes,
+}
+
+impl Profile {
+    /// Lane and `k` for `step` under this schedule and lane count.
+    #[inline]
+    pub fn lane_k(&self, step: u64) -> (u8, u64) {
+        self.lanes.lane_k(&self.schedule, step)
+    }
+}
@@ -0,0 +1,387 @@
+//! CVP2 container: versioned header with feature flags and typed TLV sections.
+//!
+//! ```text
+//! magic     4   b"CVP2"
+//! version   u8  format version (VERSION)
+//! reserved  u8  0
+//! hdr_len   u16 header size in bytes, magic included
+//! flags     u32 required feature flags; unknown bits are rejected
+//! W, H      u32, u32  canvas geometry, powers of two (see CanvasConfig)
+//! N         u64
+//! RG_LIMIT  u64
+//! schedule  u8  lane schedule id  } hdr_len >= 45; read only under
+//! sched_arg u64 its parameter     } FLAG_LANE_SCHEDULE, else linear
+//! pad_bits  u8  hdr_len >= 46; padding in the last 9-bit symbol under
+//!               FLAG_SYMBOLS_9 (the schedule fields must then be zero if unused)
+//! ...           fields added later extend hdr_len; readers skip what they don't know
+//!
+//! sections until EOF: tag u16, len u64, body[len]
+//! ```
+//!
+//! Unknown section tags are skipped, so optional data can be added without
+//! breaking old readers. Anything a reader must understand goes in `flags`.
+
+use std::io::Write;
+
+use crate::sections::{self, malformed, Counter, Reader};
+use crate::view::CanvasView;
+use crate::{
+    Alphabet, Baseline, CanvasConfig, Container, CvpError, DecodeOptions, Integrity, Lanes, Metadata, Profile, Result,
+    Schedule, RG_LIMIT_EXACT,
+};
+
+pub const MAGIC: &[u8; 4] = b"CVP2";
+pub const VERSION: u8 = 1;
+pub const HEADER_LEN: u16 = 36;
+/// Header length with the lane schedule fields.
+pub const HEADER_LEN_SCHEDULE: u16 = HEADER_LEN + 1 + 8;
+/// Header length with the 9-bit symbol padding length.
+pub const HEADER_LEN_SYMBOLS: u16 = HEADER_LEN_SCHEDULE + 1;
+/// tag u16 + len u64
+pub const SECTION_HEADER_LEN: usize = 10;
+/// RG planes omitted; readers recompute them from A.
+pub const FLAG_RG_ELIDED: u32 = 1 << 0;
+/// A section uses the varint/delta encoding instead of the CVP1 layout.
+pub const FLAG_A_VARINT: u32 = 1 << 1;
+/// Lanes start at 0 and encode adds `k` (ZERO baseline) instead of FULL.
+pub const FLAG_BASELINE_ZERO: u32 = 1 << 2;
+/// Steps use the lane schedule named in the header instead of linear.
+pub const FLAG_LANE_SCHEDULE: u32 = 1 << 3;
+/// B and alpha planes follow R and G; steps are spread with `FourLane`.
+pub const FLAG_FOUR_LANES: u32 = 1 << 4;
+/// Steps carry 9-bit symbols (`Alphabet::Nine`) instead of bytes.
+pub const FLAG_SYMBOLS_9: u32 = 1 << 5;
+pub const KNOWN_FLAGS: u32 =
+    FLAG_RG_ELIDED | FLAG_A_VARINT | FLAG_BASELINE_ZERO | FLAG_LANE_SCHEDULE | FLAG_FOUR_LANES | FLAG_SYMBOLS_9;
+/// Flags that describe the `Profile` rather than the encoding.
+pub const PROFILE_FLAGS: u32 = FLAG_BASELINE_ZERO | FLAG_LANE_SCHEDULE | FLAG_FOUR_LANES;
+
+/// R plane then G plane (then B, alpha under FLAG_FOUR_LANES), W * H u64
+/// each. Absent under FLAG_RG_ELIDED.
+pub const SEC_RG: u16 = 1;
+/// A bitset: CVP1 entry layout, or the varint/delta layout under FLAG_A_VARINT.
+pub const SEC_A: u16 = 2;
+/// Payload integrity trailer: CRC32 (u32) then SHA-256 (32 bytes).
+pub const SEC_INTEGRITY: u16 = 3;
+/// Optional filename, MIME type, creation time and key/value pairs; see `metadata`.
+pub const SEC_METADATA: u16 = 4;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Header {
+    pub version: u8,
+    /// Every flag as written, including the ones `profile` implies
+    /// (FLAG_BASELINE_ZERO, FLAG_LANE_SCHEDULE, FLAG_FOUR_LANES).
+    pub flags: u32,
+    /// W and H, plus the baseline, schedule and lanes named by the flags
+    /// and schedule fields.
+    pub profile: Profile,
+    pub n: u64,
+    pub rg_limit: u64,
+    /// Zero-padding bits in the last symbol; 0 unless FLAG_SYMBOLS_9 is set.
+    pub pad_bits: u8,
+}
+
+impl Header {
+    /// Header for the default profile (512x512, FULL, linear, two lanes).
+    pub fn new(n: u64) -> Self {
+        Self::with_profile(n, Profile::default())
+    }
+
+    /// Header for `profile`, with the flags it needs.
+    pub fn with_profile(n: u64, profile: Profile) -> Self {
+        Self { version: VERSION, flags: profile_flags(&profile), profile, n, rg_limit: RG_LIMIT_EXACT, pad_bits: 0 }
+    }
+
+    pub fn alphabet(&self) -> Alphabet {
+        if self.flags & FLAG_SYMBOLS_9 != 0 { Alphabet::Nine } else { Alphabet::Byte }
+    }
+
+    /// Decoded payload length in bytes (N unless symbols are 9-bit).
+    pub fn payload_len(&self) -> u64 {
+        self.alphabet().payload_len(self.n, self.pad_bits)
+    }
+
+    /// Size of this header as written: only the fields its flags need.
+    pub fn encoded_len(&self) -> u16 {
+        if self.flags & FLAG_SYMBOLS_9 != 0 {
+            HEADER_LEN_SYMBOLS
+        } else if self.flags & FLAG_LANE_SCHEDULE != 0 {
+            HEADER_LEN_SCHEDULE
+        } else {
+            HEADER_LEN
+        }
+    }
+}
+
+/// Flags a profile needs; the header flags must match them under PROFILE_FLAGS.
+pub fn profile_flags(profile: &Profile) -> u32 {
+    let mut flags = 0;
+    if profile.baseline == Baseline::Zero {
+        flags |= FLAG_BASELINE_ZERO;
+    }
+    if profile.schedule != Schedule::Linear {
+        flags |= FLAG_LANE_SCHEDULE;
+    }
+    if profile.lanes == Lanes::Four {
+        flags |= FLAG_FOUR_LANES;
+    }
+    flags
+}
+
+/// One TLV section; `offset` is the absolute offset of `body` in the file.
+#[derive(Clone, Copy, Debug)]
+pub struct Section<'a> {
+    pub tag: u16,
+    pub offset: usize,
+    pub body: &'a [u8],
+}
+
+/// Parse and validate the header. Returns it with the offset of the first section.
+pub fn read_header(raw: &[u8]) -> Result<(Header, usize)> {
+    if raw.len() < 4 { return Err(CvpError::TooSmall { len: raw.len() }); }
+    if &raw[0..4] != MAGIC {
+        return Err(CvpError::BadMagic { found: raw[0..4].try_into().unwrap() });
+    }
+    let mut rd = Reader::new(raw, 4);
+
+    let version = rd.u8("version")?;
+    if version != VERSION { return Err(CvpError::UnsupportedVersion { version }); }
+    let at = rd.off;
+    let reserved = rd.u8("reserved")?;
+    if reserved != 0 { return Err(malformed(at, "reserved", reserved as u64, "must be 0")); }
+    let at = rd.off;
+    let hdr_len = rd.u16("hdr_len")?;
+    if hdr_len < HEADER_LEN {
+        return Err(malformed(at, "hdr_len", hdr_len as u64, "shorter than version header"));
+    }
+    if hdr_len as usize > raw.len() {
+        return Err(CvpError::Truncated { offset: raw.len(), field: "header" });
+    }
+    let flags = rd.u32("flags")?;
+    if flags & !KNOWN_FLAGS != 0 { return Err(CvpError::UnsupportedFlags { flags }); }
+
+    let w = rd.u32("W")?;
+    let h = rd.u32("H")?;
+    let n_at = rd.off;
+    let n = rd.u64("N")?;
+    let rg_limit = rd.u64("RG_LIMIT")?;
+
+    let cfg = CanvasConfig::new(w, h)?;
+    if n == 0 { return Err(malformed(n_at, "N", 0, "zero steps")); }
+    if rg_limit != RG_LIMIT_EXACT { return Err(CvpError::RgLimitMismatch { found: rg_limit }); }
+
+    let mut schedule = Schedule::Linear;
+    let mut pad_bits = 0;
+    if flags & FLAG_SYMBOLS_9 != 0 {
+        if hdr_len < HEADER_LEN_SYMBOLS {
+            return Err(malformed(6, "hdr_len", hdr_len as u64, "too short for symbol padding"));
+        }
+        Alphabet::Nine.check(cfg)?;
+        let at = HEADER_LEN_SCHEDULE as usize;
+        pad_bits = Reader::new(raw, at).u8("pad_bits")?;
+        // 9N - pad must be whole bytes with at most one symbol of padding
+        if pad_bits > 8 || n % 8 != pad_bits as u64 % 8 {
+            return Err(malformed(at, "pad_bits", pad_bits as u64, "does not match N"));
+        }
+    }
+    if flags & FLAG_LANE_SCHEDULE != 0 {
+        if hdr_len < HEADER_LEN_SCHEDULE {
+            return Err(malformed(6, "hdr_len", hdr_len as u64, "too short for lane schedule"));
+        }
+        let id = rd.u8("schedule")?;
+        let param = rd.u64("schedule param")?;
+        schedule = Schedule::from_id(id, param)?;
+        if schedule == Schedule::Linear {
+            return Err(malformed(rd.off - 9, "schedule", 0, "linear schedule under FLAG_LANE_SCHEDULE"));
+        }
+    } else if flags & FLAG_SYMBOLS_9 != 0 {
+        // the schedule fields only pad the header out to pad_bits
+        let at = rd.off;
+        let (id, param) = (rd.u8("schedule")?, rd.u64("schedule param")?);
+        if id != 0 || param != 0 {
+            let value = if id != 0 { id as u64 } else { param };
+            return Err(malformed(at, "schedule", value, "set without FLAG_LANE_SCHEDULE"));
+        }
+    }
+
+    let baseline = if flags & FLAG_BASELINE_ZERO != 0 { Baseline::Zero } else { Baseline::Full };
+    let lanes = if flags & FLAG_FOUR_LANES != 0 { Lanes::Four } else { Lanes::Two };
+    let profile = Profile { cfg, baseline, schedule, lanes };
+    Ok((Header { version, flags, profile, n, rg_limit, pad_bits }, hdr_len as usize))
+}
+
+/// Walk the TLV sections starting at `start` up to EOF.
+pub fn sections(raw: &[u8], start: usize) -> Result<Vec<Section<'_>>> {
+    let mut rd = Reader::new(raw, start);
+    let mut out = Vec::new();
+    while rd.remaining() > 0 {
+        let tag = rd.u16("section tag")?;
+        let at = rd.off;
+        let len = rd.u64("section len")?;
+        if len > rd.remaining() as u64 {
+            return Err(malformed(at, "section len", len, "exceeds remaining bytes"));
+        }
+        let offset = rd.off;
+        let body = rd.bytes(len as usize, "section body")?;
+        out.push(Section { tag, offset, body });
+    }
+    Ok(out)
+}
+
+fn write_header<W: Write>(out: &mut W, h: &Header) -> Result<()> {
+    out.write_all(MAGIC)?;
+    out.write_all(&[h.version, 0])?;
+    out.write_all(&h.encoded_len().to_le_bytes())?;
+    out.write_all(&h.flags.to_le_bytes())?;
+    out.write_all(&h.profile.cfg.w().to_le_bytes())?;
+    out.write_all(&h.profile.cfg.h().to_le_bytes())?;
+    out.write_all(&h.n.to_le_bytes())?;
+    out.write_all(&h.rg_limit.to_le_bytes())?;
+    if h.encoded_len() >= HEADER_LEN_SCHEDULE {
+        out.write_all(&[h.profile.schedule.id()])?;
+        out.write_all(&h.profile.schedule.param().to_le_bytes())?;
+    }
+    if h.flags & FLAG_SYMBOLS_9 != 0 {
+        out.write_all(&[h.pad_bits])?;
+    }
+    Ok(())
+}
+
+fn write_section_header<W: Write>(out: &mut W, tag: u16, len: u64) -> Result<()> {
+    out.write_all(&tag.to_le_bytes())?;
+    out.write_all(&len.to_le_bytes())?;
+    Ok(())
+}
+
+fn write_a_body<W: Write>(out: &mut W, c: &Container) -> Result<()> {
+    if c.header.flags & FLAG_A_VARINT != 0 {
+        sections::write_a_varint(out, &c.a)
+    } else {
+        sections::write_a(out, &c.a, "CVP2 plain A")
+    }
+}
+
+/// CVP2 packing (canonical: sections in tag order, A entries sorted), except
+/// that the integrity trailer, when present, is always the last section.
+/// The plain A layout stores pages as u32 and page counts as u16, so N beyond
+/// 2^38 or a pixel with more than 65535 pages needs FLAG_A_VARINT; otherwise
+/// this fails with `FormatLimit`.
+pub fn pack(c: &Container) -> Result<Vec<u8>> {
+    let mut out = Vec::new();
+    pack_to(c, &mut out)?;
+    Ok(out)
+}
+
+/// Stream a container to `out`, returning the number of bytes written.
+/// Section lengths are computed up front (the A section is serialized once
+/// into a byte counter), so nothing but the container itself is held.
+pub fn pack_to<W: Write>(c: &Container, mut out: W) -> Result<u64> {
+    let profile = c.header.profile;
+    if profile != c.rg.profile {
+        return Err(malformed(0, "flags", c.header.flags as u64, "header profile disagrees with RG canvas"));
+    }
+    if c.header.flags & PROFILE_FLAGS != profile_flags(&profile) {
+        return Err(malformed(0, "flags", c.header.flags as u64, "profile flags disagree with header profile"));
+    }
+    let cfg = profile.cfg;
+    let symbols = c.header.flags & FLAG_SYMBOLS_9 != 0;
+    if symbols {
+        Alphabet::Nine.check(cfg)?;
+    }
+    let pad = c.header.pad_bits;
+    if (!symbols && pad != 0) || pad > 8 || (symbols && c.header.n % 8 != pad as u64 % 8) {
+        return Err(malformed(0, "pad_bits", pad as u64, "disagrees with flags or N"));
+    }
+    let mut written = c.header.encoded_len() as u64;
+    write_header(&mut out, &c.header)?;
+
+    if c.header.flags & FLAG_RG_ELIDED == 0 {
+        let len = (profile.lanes.count() * cfg.pixels() * 8) as u64;
+        write_section_header(&mut out, SEC_RG, len)?;
+        sections::write_planes(&mut out, &c.rg)?;
+        written += SECTION_HEADER_LEN as u64 + len;
+    }
+
+    let mut counter = Counter::default();
+    write_a_body(&mut counter, c)?;
+    write_section_header(&mut out, SEC_A, counter.0)?;
+    write_a_body(&mut out, c)?;
+    written += SECTION_HEADER_LEN as u64 + counter.0;
+
+    if let Some(meta) = &c.metadata {
+        let body = meta.to_bytes();
+        write_section_header(&mut out, SEC_METADATA, body.len() as u64)?;
+        out.write_all(&body)?;
+        written += (SECTION_HEADER_LEN + body.len()) as u64;
+    }
+
+    if let Some(t) = &c.integrity {
+        write_section_header(&mut out, SEC_INTEGRITY, 4 + 32)?;
+        out.write_all(&t.crc32.to_le_bytes())?;
+        out.write_all(&t.sha256)?;
+        written += SECTION_HEADER_LEN as u64 + 4 + 32;
+    }
+
+    out.flush()?;
+    Ok(written)
+}
+
+/// CVP2 unpacking with the same validation as CVP1. Unknown sections are
+/// skipped; RG and A must each appear exactly once, the trailer at most once.
+/// Under FLAG_RG_ELIDED the RG section must be absent and the planes are
+/// recomputed from A.
+pub fn unpack(raw: &[u8]) -> Result<Container> {
+    view(raw, &DecodeOptions::default())?.to_container()
+}
+
+/// Validate a CVP2 file and borrow its sections; see `unpack`. The canvas
+/// limit is checked first: an RG-elided file is small whatever its canvas,
+/// and its planes are only allocated later, by `to_container`.
+pub(crate) fn view<'a>(raw: &'a [u8], opts: &DecodeOptions) -> Result<CanvasView<'a>> {
+    let (header, start) = read_header(raw)?;
+    let Profile { cfg, lanes, .. } = header.profile;
+    opts.check_canvas(cfg)?;
+
+    let mut planes = None;
+    let mut a = None;
+    let mut integrity = None;
+    let mut metadata = None;
+    for sec in sections(raw, start)? {
+        let end = sec.offset + sec.body.len();
+        let mut rd = Reader::new(&raw[..end], sec.offset);
+        let slot_taken = match sec.tag {
+            SEC_RG => planes.replace(sections::check_planes(&mut rd, cfg.pixels(), lanes.count())?).is_some(),
+            SEC_A => {
+                if header.flags & FLAG_A_VARINT != 0 {
+                    sections::walk_a_varint(&mut rd, header.n, cfg, header.alphabet(), |_, _, _| {})?
+                } else {
+                    sections::walk_a(&mut rd, header.n, cfg, header.alphabet(), |_, _, _| {})?
+                };
+                a.replace(sec.body).is_some()
+            }
+            SEC_INTEGRITY => {
+                let crc32 = rd.u32("crc32")?;
+                let sha256 = rd.bytes(32, "sha256")?.try_into().unwrap();
+                integrity.replace(Integrity { crc32, sha256 }).is_some()
+            }
+            SEC_METADATA => metadata.replace(Metadata::read(&mut rd)?).is_some(),
+            _ => continue,
+        };
+        if slot_taken {
+            return Err(malformed(sec.offset - SECTION_HEADER_LEN, "section tag", sec.tag as u64, "duplicated"));
+        }
+        if rd.remaining() != 0 {
+            return Err(malformed(rd.off, "trailing bytes", rd.remaining() as u64, "inside section"));
+        }
+    }
+
+    let a = a.ok_or(CvpError::MissingSection { tag: SEC_A })?;
+    if header.flags & FLAG_RG_ELIDED != 0 {
+        if planes.is_some() {
+            return Err(malformed(start, "section tag", SEC_RG as u64, "RG planes in RG-elided file"));
+        }
+    } else if planes.is_none() {
+        return Err(CvpError::MissingSection { tag: SEC_RG });
+    }
+    Ok(CanvasView { header, planes, a, integrity, metadata })
+}
@@ -0,0 +1,171 @@
+use std::fmt;
+
+use crate::lane_name;
+
+/// Why an A-bitset operation found the bitset inconsistent with the step.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AMismatch {
+    MissingPidx,
+    MissingPage,
+    BitAlreadyClear,
+}
+
+/// Codec error. Offsets are byte offsets into the RAW input.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CvpError {
+    // --- header ---
+    TooSmall { len: usize },
+    BadMagic { found: [u8; 4] },
+    BadDims { w: u32, h: u32 },
+    RgLimitMismatch { found: u64 },
+    EmptyPayload,
+    UnsupportedVersion { version: u8 },
+    /// Required feature flags this reader does not implement.
+    UnsupportedFlags { flags: u32 },
+    MissingSection { tag: u16 },
+    /// Lane schedule id/parameter this reader does not implement.
+    UnsupportedSchedule { id: u8, param: u64 },
+    /// `value` of `field` does not fit `format` (at most `max`).
+    FormatLimit { format: &'static str, field: &'static str, value: u64, max: u64 },
+    /// The canvas has more pixels than `DecodeOptions::max_pixels` allows.
+    CanvasTooLarge { pixels: u64, max: u64 },
+
+    // --- structure ---
+    /// Input ended while reading `field` at `offset`.
+    Truncated { offset: usize, field: &'static str },
+    /// `field` at `offset` holds an invalid `value`.
+    Malformed { offset: usize, field: &'static str, value: u64, reason: &'static str },
+    /// Total number of set bits in A does not match N.
+    StepCountMismatch { bits: u64, n: u64 },
+    /// Requested payload range `start..end` is inverted or exceeds `len`.
+    RangeOutOfBounds { start: u64, end: u64, len: u64 },
+
+    // --- A bitset ---
+    AMismatch { step: u64, pidx: u32, kind: AMismatch },
+    PidxOutOfRange { pidx: u32 },
+    StepCollision { step: u64 },
+    MissingStep { step: u64 },
+
+    // --- lanes (0=R, 1=G); FULL underflows on encode, ZERO on decode ---
+    Underflow { lane: u8, step: u64, pidx: u32 },
+    Overflow { lane: u8, step: u64, pidx: u32 },
+
+    // --- convergence ---
+    ANotEmpty { remaining: usize },
+    /// Cross-check found `count` inconsistent lanes; the first one is reported.
+    RgInconsistent { lane: u8, pidx: u32, expected: u64, stored: u64, count: usize },
+    RgNotFull { lane: u8, pidx: u32, value: u64 },
+    /// ZERO-baseline counterpart of `RgNotFull`.
+    RgNotZero { lane: u8, pidx: u32, value: u64 },
+    /// The last 9-bit symbol has a non-zero bit in its `pad_bits` of padding.
+    PaddingNotZero { symbol: u16, pad_bits: u8 },
+
+    // --- integrity ---
+    /// Peel converged but the recovered bytes do not match the stored digests;
+    /// each flag is true if that digest differs.
+    IntegrityMismatch { crc32: bool, sha256: bool },
+
+    // --- chains ---
+    /// Canvas `index` (0-based, manifest order) of a chained container failed.
+    Chain { index: u32, error: Box<CvpError> },
+    /// The chain digest does not match the manifest and the canvases'
+    /// integrity trailers: canvases were reordered, swapped or altered.
+    ChainDigestMismatch,
+
+    // --- archives ---
+    /// Archive entry path rejected for `reason`.
+    BadEntryPath { path: String, reason: &'static str },
+    EntryNotFound { path: String },
+
+    // --- streaming ---
+    /// I/O failure from a reader or writer.
+    Io { kind: std::io::ErrorKind, message: String },
+}
+
+impl From<std::io::Error> for CvpError {
+    fn from(e: std::io::Error) -> Self {
+        CvpError::Io { kind: e.kind(), message: e.to_string() }
+    }
+}
+
+impl fmt::Display for CvpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use CvpError::*;
+        match self {
+            TooSmall { len } => write!(f, "raw too small: {} bytes", len),
+            BadMagic { found } => write!(f, "bad magic: {:?}", found),
+            BadDims { w, h } => write!(f, "bad dims: {}x{}", w, h),
+            RgLimitMismatch { found } => write!(f, "RG_LIMIT mismatch: {}", found),
+            EmptyPayload => write!(f, "empty payload"),
+            UnsupportedVersion { version } => write!(f, "unsupported container version: {}", version),
+            UnsupportedFlags { flags } => write!(f, "unsupported feature flags: {:#010x}", flags),
+            MissingSection { tag } => write!(f, "missing required section: tag {}", tag),
+            UnsupportedSchedule { id, param } => write!(f, "unsupported lane schedule: id {} param {}", id, param),
+            FormatLimit { format, field, value, max } => {
+                write!(f, "{} limit exceeded: {} = {} (max {})", format, field, value, max)
+            }
+            CanvasTooLarge { pixels, max } => {
+                write!(f, "canvas of {} pixels exceeds the decode limit of {}", pixels, max)
+            }
+            Truncated { offset, field } => write!(f, "unexpected eof at offset {} reading {}", offset, field),
+            Malformed { offset, field, value, reason } => {
+                write!(f, "malformed at offset {}: {} = {} ({})", offset, field, value, reason)
+            }
+            StepCountMismatch { bits, n } => write!(f, "malformed A: {} steps set, N = {}", bits, n),
+            RangeOutOfBounds { start, end, len } => {
+                write!(f, "range {}..{} out of bounds for payload of {} bytes", start, end, len)
+            }
+            AMismatch { step, pidx, kind } => {
+                let what = match kind {
+                    self::AMismatch::MissingPidx => "missing pidx",
+                    self::AMismatch::MissingPage => "missing page",
+                    self::AMismatch::BitAlreadyClear => "bit already 0",
+                };
+                write!(f, "A mismatch: {} (step={}, pidx={})", what, step, pidx)
+            }
+            PidxOutOfRange { pidx } => write!(f, "pidx out of range in A: pidx={}", pidx),
+            StepCollision { step } => write!(f, "step collision in A: step={}", step),
+            MissingStep { step } => write!(f, "missing step in A: step={}", step),
+            Underflow { lane, step, pidx } => {
+                write!(f, "{} underflow (step={}, pidx={})", lane_name(*lane), step, pidx)
+            }
+            Overflow { lane, step, pidx } => {
+                write!(f, "{} overflow (step={}, pidx={})", lane_name(*lane), step, pidx)
+            }
+            ANotEmpty { remaining } => write!(f, "A not empty after decode: {} pidx remain", remaining),
+            RgInconsistent { lane, pidx, expected, stored, count } => write!(
+                f,
+                "RG inconsistent with A: {}[{}] expected {} stored {} ({} mismatches)",
+                lane_name(*lane),
+                pidx,
+                expected,
+                stored,
+                count
+            ),
+            RgNotFull { lane, pidx, value } => {
+                write!(f, "RG not FULL after decode: {}[{}] = {}", lane_name(*lane), pidx, value)
+            }
+            RgNotZero { lane, pidx, value } => {
+                write!(f, "RG not 0 after decode: {}[{}] = {}", lane_name(*lane), pidx, value)
+            }
+            PaddingNotZero { symbol, pad_bits } => {
+                write!(f, "padding not zero after decode: last symbol {:#05x}, {} pad bits", symbol, pad_bits)
+            }
+            IntegrityMismatch { crc32, sha256 } => {
+                let which = match (crc32, sha256) {
+                    (true, true) => "CRC32 and SHA-256",
+                    (true, false) => "CRC32",
+                    _ => "SHA-256",
+                };
+                write!(f, "payload integrity mismatch: {} differ", which)
+            }
+            Chain { index, error } => write!(f, "chain canvas {}: {}", index, error),
+            ChainDigestMismatch => write!(f, "chain digest does not match the canvases"),
+            BadEntryPath { path, reason } => write!(f, "bad archive entry path {:?}: {}", path, reason),
+            EntryNotFound { path } => write!(f, "no archive entry {:?}", path),
+            Io { message, .. } => write!(f, "i/o error: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for CvpError {}
@@ -0,0 +1,106 @@
+//! Looking at a container without decoding it.
+//!
+//! `inspect` validates the structure (as `CanvasView::new` does) but never
+//! allocates planes or runs the peel, so it costs one pass over the A
+//! section whatever the canvas size. Canonicality needs a full unpack and
+//! re-pack, so it is only checked when asked for with `inspect_with`.
+
+use crate::{config, cvp2, raw_is_canonical, CanvasView, DecodeOptions, Integrity, Metadata, Result, MAGIC};
+
+/// Bytes one part of the file takes, TLV header included.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SectionSize {
+    /// "header", "RG", "A", "integrity", "metadata" or "unknown".
+    pub name: &'static str,
+    /// Section tag; `None` for the header and for CVP1's untagged sections.
+    pub tag: Option<u16>,
+    pub bytes: u64,
+}
+
+#[derive(Clone, Debug)]
+pub struct Inspection {
+    /// "CVP1" or "CVP2".
+    pub format: &'static str,
+    /// CVP1 files are reported with the equivalent CVP2 header.
+    pub header: cvp2::Header,
+    /// Decoded payload length in bytes.
+    pub payload_len: u64,
+    /// Pixels erased by at least one step; each has one A entry.
+    pub a_entries: u64,
+    /// `(page, mask)` words across all A entries.
+    pub total_pages: u64,
+    pub file_len: u64,
+    /// In file order; sums to `file_len`.
+    pub sections: Vec<SectionSize>,
+    pub integrity: Option<Integrity>,
+    pub metadata: Option<Metadata>,
+    /// True if the file is byte-identical to its canonical serialization;
+    /// `None` unless requested from `inspect_with`.
+    pub canonical: Option<bool>,
+}
+
+impl Inspection {
+    /// Pixels on the canvas.
+    pub fn pixels(&self) -> u64 {
+        self.header.profile.cfg.pixels() as u64
+    }
+}
+
+fn section_name(tag: u16) -> &'static str {
+    match tag {
+        cvp2::SEC_RG => "RG",
+        cvp2::SEC_A => "A",
+        cvp2::SEC_INTEGRITY => "integrity",
+        cvp2::SEC_METADATA => "metadata",
+        _ => "unknown",
+    }
+}
+
+/// Header fields, A statistics, size breakdown, integrity digests and
+/// metadata of a CVP1/CVP2 file. Any canvas the format allows is inspected,
+/// as no planes are allocated.
+pub fn inspect(raw: &[u8]) -> Result<Inspection> {
+    inspect_with(raw, false)
+}
+
+/// `inspect`, also checking canonical form if `canonical` is set. That check
+/// unpacks the file, within the default `DecodeOptions::max_pixels`.
+pub fn inspect_with(raw: &[u8], canonical: bool) -> Result<Inspection> {
+    let opts = DecodeOptions { max_pixels: config::MAX_PIXELS as u64, ..Default::default() };
+    let view = CanvasView::with_options(raw, &opts)?;
+
+    let (mut a_entries, mut total_pages) = (0, 0);
+    for entry in view.a_entries() {
+        a_entries += 1;
+        total_pages += entry.pages.count() as u64;
+    }
+
+    let (format, sections) = if &raw[0..4] == MAGIC {
+        let rg = view.planes.map_or(0, |p| p.len()) as u64;
+        let header = (raw.len() - view.a.len()) as u64 - rg;
+        let parts = [("header", header), ("RG", rg), ("A", view.a.len() as u64)];
+        ("CVP1", parts.iter().map(|&(name, bytes)| SectionSize { name, tag: None, bytes }).collect())
+    } else {
+        let (_, start) = cvp2::read_header(raw)?;
+        let mut sections = vec![SectionSize { name: "header", tag: None, bytes: start as u64 }];
+        sections.extend(cvp2::sections(raw, start)?.iter().map(|s| SectionSize {
+            name: section_name(s.tag),
+            tag: Some(s.tag),
+            bytes: (cvp2::SECTION_HEADER_LEN + s.body.len()) as u64,
+        }));
+        ("CVP2", sections)
+    };
+
+    Ok(Inspection {
+        format,
+        header: view.header().clone(),
+        payload_len: view.header().payload_len(),
+        a_entries,
+        total_pages,
+        file_len: raw.len() as u64,
+        sections,
+        integrity: view.integrity(),
+        metadata: view.metadata().cloned(),
+        canonical: if canonical { Some(raw_is_canonical(raw)?) } else { None },
+    })
+}
@@ -1,5 +1,35 @@
-use anyhow::{bail, Result};
 use std::collections::HashMap;
+use std::ops::Range;
+
+pub mod alphabet;
+pub mod archive;
+pub mod capacity;
+pub mod chain;
+pub mod config;
+pub mod cvp2;
+mod error;
+pub mod inspect;
+pub mod integrity;
+pub mod metadata;
+pub mod schedule;
+mod sections;
+pub mod stream;
+pub mod verify;
+pub mod view;
+
+pub use alphabet::Alphabet;
+pub use capacity::{capacity_report, capacity_report_with, CapacityReport};
+pub use config::{CanvasConfig, Profile};
+pub use error::{AMismatch, CvpError};
+pub use inspect::{inspect, inspect_with, Inspection, SectionSize};
+pub use integrity::Integrity;
+pub use metadata::Metadata;
+pub use schedule::{LaneSchedule, Lanes, Schedule};
+pub use verify::{verify, Stage, Verified, VerifyError};
+pub use view::CanvasView;
+use sections::{malformed, Reader};
+
+pub type Result<T, E = CvpError> = std::result::Result<T, E>;
 
 pub const W: u32 = 512;
 pub const H: u32 = 512;
@@ -10,13 +40,25 @@ pub const RG_LIMIT: u64 = (1u64 << 63) / 16; // == floor(2^64 / 32) but safe in
 // 2^59 == (1<<63)/16
 pub const RG_LIMIT_EXACT: u64 = 1u64 << 59;
 
+/// The default (`schedule::Linear`) lane schedule.
 #[inline]
-pub fn lane_k(step: u32) -> (u8, u64) {
-    // 0=R, 1=G
+pub fn lane_k(step: u64) -> (u8, u64) {
+    // 0=R, 1=G; (step + 1) >> 1 written so it cannot overflow at u64::MAX
     if (step & 1) == 1 {
-        (0, ((step + 1) >> 1) as u64)
+        (0, (step >> 1) + 1)
     } else {
-        (1, (step >> 1) as u64)
+        (1, step >> 1)
+    }
+}
+
+/// Display name of a lane index.
+pub fn lane_name(lane: u8) -> &'static str {
+    match lane {
+        0 => "R",
+        1 => "G",
+        2 => "B",
+        3 => "alpha",
+        _ => "?",
     }
 }
 
@@ -25,26 +67,124 @@ pub fn pidx_of(x: u32, y: u32) -> u32 {
     (y << 9) + x
 }
 
+/// Value every lane starts from on encode and must return to on decode.
+/// FULL erases by subtracting `k` from RG_LIMIT; ZERO erases by adding `k`
+/// to 0 (FLAG_BASELINE_ZERO).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Baseline {
+    #[default]
+    Full,
+    Zero,
+}
+
+impl Baseline {
+    pub fn value(self) -> u64 {
+        match self {
+            Baseline::Full => RG_LIMIT_EXACT,
+            Baseline::Zero => 0,
+        }
+    }
+
+    #[inline]
+    fn add(v: u64, lane: u8, k: u64, step: u64, pidx: u32) -> Result<u64> {
+        v.checked_add(k).filter(|&v| v <= RG_LIMIT_EXACT).ok_or(CvpError::Overflow { lane, step, pidx })
+    }
+
+    /// Encode-side move of one step: away from the baseline by `k`.
+    #[inline]
+    pub(crate) fn erase(self, v: u64, lane: u8, k: u64, step: u64, pidx: u32) -> Result<u64> {
+        match self {
+            Baseline::Full => v.checked_sub(k).ok_or(CvpError::Underflow { lane, step, pidx }),
+            Baseline::Zero => Self::add(v, lane, k, step, pidx),
+        }
+    }
+
+    /// Decode-side move of one step: back towards the baseline by `k`.
+    #[inline]
+    pub(crate) fn fill(self, v: u64, lane: u8, k: u64, step: u64, pidx: u32) -> Result<u64> {
+        match self {
+            Baseline::Full => Self::add(v, lane, k, step, pidx),
+            Baseline::Zero => v.checked_sub(k).ok_or(CvpError::Underflow { lane, step, pidx }),
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct RGCanvas {
+    pub profile: Profile,
     pub r: Vec<u64>,
     pub g: Vec<u64>,
+    /// B and alpha planes; empty unless the profile has `Lanes::Four`.
+    pub b: Vec<u64>,
+    pub alpha: Vec<u64>,
 }
 
 impl RGCanvas {
+    /// Default 512x512 canvas with the FULL baseline.
     pub fn new(full: bool) -> Self {
+        Self::with_config(CanvasConfig::default(), full)
+    }
+
+    /// FULL-baseline, linear-schedule canvas, every lane RG_LIMIT if `full` else 0.
+    pub fn with_config(cfg: CanvasConfig, full: bool) -> Self {
         let init = if full { RG_LIMIT_EXACT } else { 0 };
         Self {
-            r: vec![init; PIXELS],
-            g: vec![init; PIXELS],
+            profile: Profile { cfg, ..Profile::default() },
+            r: vec![init; cfg.pixels()],
+            g: vec![init; cfg.pixels()],
+            b: Vec::new(),
+            alpha: Vec::new(),
+        }
+    }
+
+    /// Canvas with every lane at the profile's baseline.
+    pub fn at_baseline(profile: Profile) -> Self {
+        let init = profile.baseline.value();
+        let pixels = profile.cfg.pixels();
+        let extra = if profile.lanes == Lanes::Four { pixels } else { 0 };
+        Self {
+            profile,
+            r: vec![init; pixels],
+            g: vec![init; pixels],
+            b: vec![init; extra],
+            alpha: vec![init; extra],
+        }
+    }
+
+    /// Lane and `k` for `step` under this canvas's schedule and lane count.
+    #[inline]
+    pub fn lane_k(&self, step: u64) -> (u8, u64) {
+        self.profile.lane_k(step)
+    }
+
+    pub fn plane(&self, lane: u8) -> &[u64] {
+        match lane {
+            0 => &self.r,
+            1 => &self.g,
+            2 => &self.b,
+            _ => &self.alpha,
+        }
+    }
+
+    pub fn plane_mut(&mut self, lane: u8) -> &mut [u64] {
+        match lane {
+            0 => &mut self.r,
+            1 => &mut self.g,
+            2 => &mut self.b,
+            _ => &mut self.alpha,
         }
     }
+
+    /// The active planes in lane order (R, G[, B, alpha]).
+    pub fn planes(&self) -> impl Iterator<Item = &[u64]> {
+        (0..self.profile.lanes.count() as u8).map(|lane| self.plane(lane))
+    }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Default)]
 pub struct ABitset {
-    // pidx -> pages: page -> mask
-    pub db: HashMap<u32, HashMap<u32, u64>>,
+    // pidx -> pages: page -> mask (page = step >> 6)
+    pub db: HashMap<u32, HashMap<u64, u64>>,
 }
 
 impl ABitset {
@@ -53,25 +193,26 @@ impl ABitset {
     }
 
     #[inline]
-    pub fn set_step(&mut self, pidx: u32, step: u32) {
+    pub fn set_step(&mut self, pidx: u32, step: u64) {
         let page = step >> 6;
         let bit = step & 63;
         let m = 1u64 << bit;
-        let pages = self.db.entry(pidx).or_insert_with(HashMap::new);
+        let pages = self.db.entry(pidx).or_default();
         let entry = pages.entry(page).or_insert(0);
         *entry |= m;
     }
 
     #[inline]
-    pub fn clear_step(&mut self, pidx: u32, step: u32) -> Result<()> {
+    pub fn clear_step(&mut self, pidx: u32, step: u64) -> Result<()> {
         let page = step >> 6;
         let bit = step & 63;
         let m = 1u64 << bit;
 
-        let pages = self.db.get_mut(&pidx).ok_or_else(|| anyhow::anyhow!("A mismatch: missing pidx"))?;
-        let word = pages.get_mut(&page).ok_or_else(|| anyhow::anyhow!("A mismatch: missing page"))?;
+        let mismatch = |kind| CvpError::AMismatch { step, pidx, kind };
+        let pages = self.db.get_mut(&pidx).ok_or(mismatch(AMismatch::MissingPidx))?;
+        let word = pages.get_mut(&page).ok_or(mismatch(AMismatch::MissingPage))?;
         if ((*word >> bit) & 1) == 0 {
-            bail!("A mismatch: bit already 0");
+            return Err(mismatch(AMismatch::BitAlreadyClear));
         }
         *word &= !m;
 
@@ -90,23 +231,90 @@ impl ABitset {
     }
 }
 
+/// Recompute the RG planes implied by A: every pixel starts FULL and each
+/// of its steps takes `k` from its lane. Used for RG-elided files.
+pub fn rg_from_a(a: &ABitset) -> Result<RGCanvas> {
+    rg_from_a_with(a, Profile::default())
+}
+
+/// `rg_from_a` on a canvas of the given profile.
+pub fn rg_from_a_with(a: &ABitset, profile: Profile) -> Result<RGCanvas> {
+    let mut rg = RGCanvas::at_baseline(profile);
+    let baseline = profile.baseline;
+    for (&pidx, pages) in a.db.iter() {
+        if pidx as usize >= profile.cfg.pixels() {
+            return Err(CvpError::PidxOutOfRange { pidx });
+        }
+        for (&page, &mask) in pages.iter() {
+            let mut m = mask;
+            while m != 0 {
+                let step = (page << 6) + m.trailing_zeros() as u64;
+                let (lane, k) = rg.lane_k(step);
+                let plane = rg.plane_mut(lane);
+                plane[pidx as usize] = baseline.erase(plane[pidx as usize], lane, k, step, pidx)?;
+                m &= m - 1;
+            }
+        }
+    }
+    Ok(rg)
+}
+
+/// A pixel whose stored lane value disagrees with the value implied by A
+/// (the baseline moved by the sum of that lane's `k` over the pixel's steps).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RgMismatch {
+    pub pidx: u32,
+    /// 0=R, 1=G, 2=B, 3=alpha
+    pub lane: u8,
+    pub expected: u64,
+    pub stored: u64,
+}
+
+/// Cross-check the RG planes against A without peeling. Returns every
+/// mismatching (pixel, lane), ordered by lane then pidx; empty if consistent.
+pub fn verify_rg_against_a(rg: &RGCanvas, a: &ABitset) -> Result<Vec<RgMismatch>> {
+    let expected = rg_from_a_with(a, rg.profile)?;
+    let mut out = Vec::new();
+    for (lane, (exp, got)) in expected.planes().zip(rg.planes()).enumerate() {
+        for (pidx, (&e, &s)) in exp.iter().zip(got.iter()).enumerate() {
+            if e != s {
+                out.push(RgMismatch { pidx: pidx as u32, lane: lane as u8, expected: e, stored: s });
+            }
+        }
+    }
+    Ok(out)
+}
+
 /// Build step->pidx index by scanning A exactly once.
 /// No step_to_x stored in RAW. Cache is in-memory only.
+/// Every pidx must lie on the default 512x512 canvas.
 pub fn build_step_index_from_a(a: &ABitset, n: u32) -> Result<Vec<u32>> {
-    let mut step_to_pidx = vec![0u32; (n as usize) + 1];
-    let mut seen = vec![0u8; (n as usize) + 1];
+    build_step_index_from_a64(a, n as u64, CanvasConfig::default())
+}
+
+/// `build_step_index_from_a` for N beyond u32 on a canvas of any geometry.
+/// The index holds N + 1 entries, so N must also fit in memory (usize).
+pub fn build_step_index_from_a64(a: &ABitset, n: u64, cfg: CanvasConfig) -> Result<Vec<u32>> {
+    let len = usize::try_from(n)
+        .ok()
+        .and_then(|n| n.checked_add(1))
+        .ok_or(CvpError::FormatLimit { format: "memory", field: "N", value: n, max: usize::MAX as u64 - 1 })?;
+    let mut step_to_pidx = vec![0u32; len];
+    let mut seen = vec![0u8; len];
 
     for (&pidx, pages) in a.db.iter() {
+        if pidx as usize >= cfg.pixels() {
+            return Err(CvpError::PidxOutOfRange { pidx });
+        }
         for (&page, &mask) in pages.iter() {
             let base = page << 6;
             let mut m = mask;
             while m != 0 {
-                let tz = m.trailing_zeros() as u32;
-                let step = base + tz;
+                let step = base + m.trailing_zeros() as u64;
                 if step >= 1 && step <= n {
                     let idx = step as usize;
                     if seen[idx] != 0 {
-                        bail!("step collision in A: step={}", step);
+                        return Err(CvpError::StepCollision { step });
                     }
                     seen[idx] = 1;
                     step_to_pidx[idx] = pidx;
@@ -116,17 +324,37 @@ pub fn build_step_index_from_a(a: &ABitset, n: u32) -> Result<Vec<u32>> {
         }
     }
 
-    for s in 1..=n as usize {
-        if seen[s] == 0 {
-            bail!("missing step in A: step={}", s);
-        }
+    if let Some(s) = seen.iter().skip(1).position(|&v| v == 0) {
+        return Err(CvpError::MissingStep { step: s as u64 + 1 });
     }
 
     Ok(step_to_pidx)
 }
 
-/// CVP1 RAW packing
-pub fn raw_pack(rg: &RGCanvas, a: &ABitset, n: u32) -> Vec<u8> {
+/// CVP1 RAW packing (canonical form); `raw_pack_with` also writes the
+/// varint A encoding.
+/// Entries are written in ascending pidx order, pages in ascending page order,
+/// so the same (RG, A, N) state always packs to the same bytes.
+/// CVP1 stores pages as u32 and page counts as u16 and only knows the
+/// 512x512 canvas and the FULL baseline; exceeding any of these is a
+/// `FormatLimit` error.
+pub fn raw_pack(rg: &RGCanvas, a: &ABitset, n: u32) -> Result<Vec<u8>> {
+    let profile = rg.profile;
+    for (field, value, max) in [("W", profile.cfg.w(), W), ("H", profile.cfg.h(), H)] {
+        if value != max {
+            return Err(CvpError::FormatLimit { format: "CVP1", field, value: value as u64, max: max as u64 });
+        }
+    }
+    if profile.baseline != Baseline::Full {
+        return Err(CvpError::FormatLimit { format: "CVP1", field: "baseline", value: 1, max: 0 });
+    }
+    if profile.schedule != Schedule::Linear {
+        let value = profile.schedule.id() as u64;
+        return Err(CvpError::FormatLimit { format: "CVP1", field: "schedule", value, max: 0 });
+    }
+    if profile.lanes != Lanes::Two {
+        return Err(CvpError::FormatLimit { format: "CVP1", field: "lanes", value: 4, max: 2 });
+    }
     let mut out = Vec::new();
 
     out.extend_from_slice(MAGIC);
@@ -135,147 +363,485 @@ pub fn raw_pack(rg: &RGCanvas, a: &ABitset, n: u32) -> Vec<u8> {
     out.extend_from_slice(&n.to_le_bytes());
     out.extend_from_slice(&RG_LIMIT_EXACT.to_le_bytes());
 
-    // planes
-    for &v in rg.r.iter() { out.extend_from_slice(&v.to_le_bytes()); }
-    for &v in rg.g.iter() { out.extend_from_slice(&v.to_le_bytes()); }
+    sections::write_planes(&mut out, rg)?;
+    sections::write_a(&mut out, a, "CVP1")?;
 
-    // A entry count
-    let entry_count = a.db.len() as u32;
-    out.extend_from_slice(&entry_count.to_le_bytes());
+    Ok(out)
+}
 
-    // entries
-    for (&pidx, pages) in a.db.iter() {
-        out.extend_from_slice(&pidx.to_le_bytes());
-        let pcnt = pages.len() as u16;
-        out.extend_from_slice(&pcnt.to_le_bytes());
-        for (&page, &mask) in pages.iter() {
-            out.extend_from_slice(&page.to_le_bytes());
-            out.extend_from_slice(&mask.to_le_bytes());
+/// Layout of the A section.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AEncoding {
+    /// u32 pidx, u16 page count, then (u32 page, u64 mask) per page.
+    #[default]
+    Plain,
+    /// Delta-coded pidx, varint pages, single-bit masks as a bit index
+    /// (FLAG_A_VARINT).
+    Varint,
+}
+
+/// `raw_pack` with a choice of A encoding. `Plain` writes CVP1 exactly as
+/// `raw_pack` does. `Varint` can only be flagged in a CVP2 header, so it
+/// writes CVP2 (without an integrity trailer, as there is no payload to
+/// hash). `raw_unpack` reads both.
+pub fn raw_pack_with(rg: &RGCanvas, a: &ABitset, n: u64, encoding: AEncoding) -> Result<Vec<u8>> {
+    match encoding {
+        AEncoding::Plain => {
+            let n = u32::try_from(n)
+                .map_err(|_| CvpError::FormatLimit { format: "CVP1", field: "N", value: n, max: u32::MAX as u64 })?;
+            raw_pack(rg, a, n)
+        }
+        AEncoding::Varint => {
+            let mut header = cvp2::Header::with_profile(n, rg.profile);
+            header.flags |= cvp2::FLAG_A_VARINT;
+            cvp2::pack(&Container { header, rg: rg.clone(), a: a.clone(), integrity: None, metadata: None })
         }
     }
+}
 
-    out
+/// CVP1 packing of a container; N must fit the u32 header field.
+fn cvp1_pack(c: &Container) -> Result<Vec<u8>> {
+    if c.header.alphabet() != Alphabet::Byte {
+        let value = c.header.alphabet().bits() as u64;
+        return Err(CvpError::FormatLimit { format: "CVP1", field: "symbol bits", value, max: 8 });
+    }
+    let n = u32::try_from(c.header.n)
+        .map_err(|_| CvpError::FormatLimit { format: "CVP1", field: "N", value: c.header.n, max: u32::MAX as u64 })?;
+    raw_pack(&c.rg, &c.a, n)
 }
 
-pub fn raw_unpack(raw: &[u8]) -> Result<(RGCanvas, ABitset, u32)> {
-    let mut off = 0usize;
+/// A parsed container. CVP1 files are presented with the equivalent CVP2 header.
+#[derive(Clone, Debug)]
+pub struct Container {
+    pub header: cvp2::Header,
+    pub rg: RGCanvas,
+    pub a: ABitset,
+    /// Digests of the original payload, if the writer stored them.
+    pub integrity: Option<Integrity>,
+    /// Filename, MIME type and the like, if the writer stored them.
+    pub metadata: Option<Metadata>,
+}
 
-    if raw.len() < 4 { bail!("raw too small"); }
-    if &raw[0..4] != MAGIC { bail!("bad magic"); }
-    off += 4;
+/// RAW unpacking with strict validation; dispatches on magic (CVP1 or CVP2).
+///
+/// Every structural inconsistency is rejected with the byte offset, field and
+/// offending value. Nothing is allocated before the bytes backing it have been
+/// bounds-checked, so hostile input can neither panic nor over-allocate:
+/// - RG plane values must be <= RG_LIMIT
+/// - power-of-two W x H within `config::MAX_PIXELS` (CVP1: 512x512 only)
+/// - pidx < W * H, no duplicate pidx, no entry without pages
+/// - no duplicate page within an entry, no zero mask
+/// - every set bit is a step in 1..=N and the total bit count equals N
+/// - no trailing bytes
+pub fn unpack(raw: &[u8]) -> Result<Container> {
+    CanvasView::new(raw)?.to_container()
+}
 
-    let read_u32 = |buf: &[u8], off: &mut usize| -> Result<u32> {
-        if *off + 4 > buf.len() { bail!("unexpected eof"); }
-        let v = u32::from_le_bytes(buf[*off..*off+4].try_into().unwrap());
-        *off += 4;
-        Ok(v)
-    };
-    let read_u16 = |buf: &[u8], off: &mut usize| -> Result<u16> {
-        if *off + 2 > buf.len() { bail!("unexpected eof"); }
-        let v = u16::from_le_bytes(buf[*off..*off+2].try_into().unwrap());
-        *off += 2;
-        Ok(v)
-    };
-    let read_u64 = |buf: &[u8], off: &mut usize| -> Result<u64> {
-        if *off + 8 > buf.len() { bail!("unexpected eof"); }
-        let v = u64::from_le_bytes(buf[*off..*off+8].try_into().unwrap());
-        *off += 8;
-        Ok(v)
-    };
+/// Like `unpack`, returning only the RG planes, A bitset and N.
+pub fn raw_unpack(raw: &[u8]) -> Result<(RGCanvas, ABitset, u64)> {
+    let c = unpack(raw)?;
+    Ok((c.rg, c.a, c.header.n))
+}
 
-    let w = read_u32(raw, &mut off)?;
-    let h = read_u32(raw, &mut off)?;
-    let n = read_u32(raw, &mut off)?;
-    let rg_limit = read_u64(raw, &mut off)?;
-
-    if w != W || h != H { bail!("bad dims: {}x{}", w, h); }
-    if rg_limit != RG_LIMIT_EXACT { bail!("RG_LIMIT mismatch"); }
-
-    let mut rg = RGCanvas::new(false);
-    for i in 0..PIXELS { rg.r[i] = read_u64(raw, &mut off)?; }
-    for i in 0..PIXELS { rg.g[i] = read_u64(raw, &mut off)?; }
-
-    let entry_count = read_u32(raw, &mut off)?;
-    let mut a = ABitset::new();
-
-    for _ in 0..entry_count {
-        let pidx = read_u32(raw, &mut off)?;
-        let pcnt = read_u16(raw, &mut off)? as u32;
-        let mut pages: HashMap<u32, u64> = HashMap::new();
-        for _ in 0..pcnt {
-            let page = read_u32(raw, &mut off)?;
-            let mask = read_u64(raw, &mut off)?;
-            pages.insert(page, mask);
-        }
-        a.db.insert(pidx, pages);
+fn cvp1_view<'a>(raw: &'a [u8], opts: &DecodeOptions) -> Result<CanvasView<'a>> {
+    let mut rd = Reader::new(raw, 4);
+
+    let w = rd.u32("W")?;
+    let h = rd.u32("H")?;
+    let n_at = rd.off;
+    let n = rd.u32("N")?;
+    let rg_limit = rd.u64("RG_LIMIT")?;
+
+    if w != W || h != H { return Err(CvpError::BadDims { w, h }); }
+    if n == 0 { return Err(malformed(n_at, "N", 0, "zero steps")); }
+    if rg_limit != RG_LIMIT_EXACT { return Err(CvpError::RgLimitMismatch { found: rg_limit }); }
+    opts.check_canvas(CanvasConfig::default())?;
+
+    let planes = sections::check_planes(&mut rd, PIXELS, 2)?;
+    let a_off = rd.off;
+    sections::walk_a(&mut rd, n as u64, CanvasConfig::default(), Alphabet::Byte, |_, _, _| {})?;
+
+    if rd.remaining() != 0 {
+        return Err(malformed(rd.off, "trailing bytes", rd.remaining() as u64, "after A section"));
+    }
+
+    Ok(CanvasView {
+        header: cvp2::Header::new(n as u64),
+        planes: Some(planes),
+        a: &raw[a_off..],
+        integrity: None,
+        metadata: None,
+    })
+}
+
+/// Convert a CVP1 file to CVP2.
+pub fn cvp1_to_cvp2(raw: &[u8]) -> Result<Vec<u8>> {
+    if raw.len() >= 4 && &raw[0..4] != MAGIC {
+        return Err(CvpError::BadMagic { found: raw[0..4].try_into().unwrap() });
+    }
+    cvp2::pack(&unpack(raw)?)
+}
+
+/// Convert a CVP2 file to CVP1. Sections CVP1 cannot hold (the integrity
+/// trailer and metadata) are dropped.
+pub fn cvp2_to_cvp1(raw: &[u8]) -> Result<Vec<u8>> {
+    if raw.len() >= 4 && &raw[0..4] != cvp2::MAGIC {
+        return Err(CvpError::BadMagic { found: raw[0..4].try_into().unwrap() });
     }
+    cvp1_pack(&unpack(raw)?)
+}
+
+/// True if `raw` is byte-identical to its canonical serialization.
+pub fn raw_is_canonical(raw: &[u8]) -> Result<bool> {
+    Ok(canonicalize(raw)? == raw)
+}
 
-    Ok((rg, a, n))
+/// Rewrite a RAW file into canonical form (entries by pidx, pages by page).
+/// The format is preserved; for CVP2, unknown sections are dropped.
+pub fn canonicalize(raw: &[u8]) -> Result<Vec<u8>> {
+    let c = unpack(raw)?;
+    if &raw[0..4] == cvp2::MAGIC {
+        cvp2::pack(&c)
+    } else {
+        cvp1_pack(&c)
+    }
 }
 
-/// Encode (Erase): start FULL, step N..1, A set then RG -= k
+/// Encoding profile for `encode_with`. The default is the profile of
+/// `encode_erase`, written as CVP2.
+#[derive(Clone, Debug, Default)]
+pub struct EncodeOptions {
+    /// Omit the RG planes (FLAG_RG_ELIDED); readers recompute them from A.
+    pub rg_elided: bool,
+    /// Write A with the varint/delta encoding (FLAG_A_VARINT). Also chosen
+    /// automatically when the plain layout cannot hold A (a pixel with more
+    /// than 65535 pages, or pages beyond u32).
+    pub a_varint: bool,
+    /// Canvas geometry, baseline (FLAG_BASELINE_ZERO for ZERO), lane schedule
+    /// (FLAG_LANE_SCHEDULE unless linear) and lanes (FLAG_FOUR_LANES). CVP1
+    /// only supports the default.
+    pub profile: Profile,
+    /// Step symbols (FLAG_SYMBOLS_9 for 9-bit); `Alphabet::Nine` needs a
+    /// canvas at least 512 wide. CVP1 only supports bytes.
+    pub alphabet: Alphabet,
+    /// Written as the metadata section; not covered by the integrity trailer.
+    pub metadata: Option<Metadata>,
+}
+
+/// Encode (Erase): start FULL, step N..1, A set then RG -= k.
+/// Output is CVP1, which every reader understands but which has no room for
+/// the integrity trailer; use `encode_with` for CVP2 (integrity trailer,
+/// compact and other profiles).
 pub fn encode_erase(payload: &[u8]) -> Result<Vec<u8>> {
-    let n = payload.len() as u32;
-    if n == 0 { bail!("empty payload"); }
+    cvp1_pack(&erase(payload, &EncodeOptions::default())?)
+}
 
-    let mut rg = RGCanvas::new(true);
-    let mut a = ABitset::new();
+/// Encode with an explicit profile into a CVP2 container with an integrity
+/// trailer.
+pub fn encode_with(payload: &[u8], opts: &EncodeOptions) -> Result<Vec<u8>> {
+    let mut c = erase(payload, opts)?;
+    c.integrity = Some(Integrity::of(payload));
+    cvp2::pack(&c)
+}
 
-    for step in (1..=n).rev() {
-        let x = payload[(step - 1) as usize] as u32;
-        let y = step & 511;
-        let pidx = pidx_of(x, y) as usize;
-
-        a.set_step(pidx as u32, step); // A first
-
-        let (lane, k) = lane_k(step);
-        if lane == 0 {
-            let v = rg.r[pidx];
-            if v < k { bail!("R underflow encode"); }
-            rg.r[pidx] = v - k;
-        } else {
-            let v = rg.g[pidx];
-            if v < k { bail!("G underflow encode"); }
-            rg.g[pidx] = v - k;
+/// Erase `payload` into a container without an integrity trailer (RG -= k,
+/// or RG += k from 0 under the ZERO baseline).
+fn erase(payload: &[u8], opts: &EncodeOptions) -> Result<Container> {
+    if payload.is_empty() { return Err(CvpError::EmptyPayload); }
+    opts.alphabet.check(opts.profile.cfg)?;
+
+    let mut state = EraseState::new(opts);
+    let (n, pad_bits) = match opts.alphabet {
+        Alphabet::Byte => {
+            let n = payload.len() as u64;
+            for step in (1..=n).rev() {
+                state.erase(step, payload[(step - 1) as usize] as u16)?;
+            }
+            (n, 0)
+        }
+        Alphabet::Nine => {
+            let (symbols, pad_bits) = alphabet::Packer::pack(payload);
+            for (i, &symbol) in symbols.iter().enumerate().rev() {
+                state.erase(i as u64 + 1, symbol)?;
+            }
+            (symbols.len() as u64, pad_bits)
+        }
+    };
+
+    Ok(state.into_container(n, pad_bits, None, opts))
+}
+
+/// Encoder canvas state. Steps may be erased in any order: the final planes
+/// are sums, so N..1 (`encode_with`) and 1..N (`stream::Encoder`) produce the
+/// same container and differ only in which step reports an underflow.
+pub(crate) struct EraseState {
+    rg: RGCanvas,
+    a: ABitset,
+}
+
+impl EraseState {
+    pub(crate) fn new(opts: &EncodeOptions) -> Self {
+        Self { rg: RGCanvas::at_baseline(opts.profile), a: ABitset::new() }
+    }
+
+    /// A set then RG moves `k` away from the baseline for one payload
+    /// symbol, which must be below the canvas width.
+    #[inline]
+    pub(crate) fn erase(&mut self, step: u64, symbol: u16) -> Result<()> {
+        let pidx = self.rg.profile.cfg.symbol_pidx(symbol, step);
+
+        self.a.set_step(pidx, step); // A first
+
+        let (lane, k) = self.rg.lane_k(step);
+        let baseline = self.rg.profile.baseline;
+        let plane = self.rg.plane_mut(lane);
+        plane[pidx as usize] = baseline.erase(plane[pidx as usize], lane, k, step, pidx)?;
+        Ok(())
+    }
+
+    pub(crate) fn into_container(
+        self,
+        n: u64,
+        pad_bits: u8,
+        integrity: Option<Integrity>,
+        opts: &EncodeOptions,
+    ) -> Container {
+        let mut header = cvp2::Header::with_profile(n, self.rg.profile);
+        header.pad_bits = pad_bits;
+        if opts.rg_elided {
+            header.flags |= cvp2::FLAG_RG_ELIDED;
+        }
+        if opts.a_varint || !sections::plain_a_fits(&self.a) {
+            header.flags |= cvp2::FLAG_A_VARINT;
         }
+        if opts.alphabet == Alphabet::Nine {
+            header.flags |= cvp2::FLAG_SYMBOLS_9;
+        }
+        Container { header, rg: self.rg, a: self.a, integrity, metadata: opts.metadata.clone() }
     }
+}
+
+/// Decoding options for `decode_with`. The default matches `decode_fill`.
+#[derive(Clone, Debug)]
+pub struct DecodeOptions {
+    /// Run `verify_rg_against_a` before the peel and fail on the first
+    /// inconsistent pixel instead of mid-peel or at the final RG check.
+    pub cross_check: bool,
+    /// Largest canvas (W * H) to accept; larger files fail with
+    /// `CanvasTooLarge` before any plane is allocated.
+    pub max_pixels: u64,
+}
 
-    Ok(raw_pack(&rg, &a, n))
+impl Default for DecodeOptions {
+    fn default() -> Self {
+        Self { cross_check: false, max_pixels: config::DEFAULT_MAX_DECODE_PIXELS }
+    }
+}
+
+impl DecodeOptions {
+    pub(crate) fn check_canvas(&self, cfg: CanvasConfig) -> Result<()> {
+        let pixels = cfg.pixels() as u64;
+        if pixels > self.max_pixels {
+            return Err(CvpError::CanvasTooLarge { pixels, max: self.max_pixels });
+        }
+        Ok(())
+    }
 }
 
 /// Decode (Fill): build step->pidx cache by 1 scan of A, then step N..1:
-/// A clear then RG += k, verify A empty and RG FULL.
+/// A clear then RG += k, verify A empty and RG FULL, then check the
+/// integrity trailer if the container has one.
 pub fn decode_fill(raw: &[u8]) -> Result<Vec<u8>> {
-    let (mut rg, mut a, n) = raw_unpack(raw)?;
-    let step_to_pidx = build_step_index_from_a(&a, n)?;
+    decode_with(raw, &DecodeOptions::default())
+}
+
+/// Decode with explicit options.
+pub fn decode_with(raw: &[u8], opts: &DecodeOptions) -> Result<Vec<u8>> {
+    decode_view(&CanvasView::with_options(raw, opts)?, opts)
+}
+
+/// Decode from an already validated view, e.g. one over a memory-mapped file.
+pub fn decode_view(view: &CanvasView, opts: &DecodeOptions) -> Result<Vec<u8>> {
+    let step_to_pidx = peel_verified(view, opts)?;
+    let mut out = Vec::with_capacity(view.header().payload_len() as usize);
+    payload_chunks(view.header(), view.config(), &step_to_pidx[1..], |chunk| {
+        out.extend_from_slice(chunk);
+        Ok(())
+    })?;
+    Ok(out)
+}
+
+/// Pass the payload spelled by `steps` (the pidx of steps 1..=N) to `sink`
+/// in chunks, joining 9-bit symbols back into bytes when the header says so.
+pub(crate) fn payload_chunks(
+    header: &cvp2::Header,
+    cfg: CanvasConfig,
+    steps: &[u32],
+    mut sink: impl FnMut(&[u8]) -> Result<()>,
+) -> Result<()> {
+    let mut left = header.payload_len();
+    let mut unpacker = alphabet::Unpacker::new();
+    let mut buf = Vec::with_capacity(64 * 1024 * 9 / 8 + 1);
+    for chunk in steps.chunks(64 * 1024) {
+        buf.clear();
+        match header.alphabet() {
+            Alphabet::Byte => buf.extend(chunk.iter().map(|&pidx| cfg.byte_of(pidx))),
+            Alphabet::Nine => chunk.iter().for_each(|&pidx| unpacker.push(cfg.symbol_of(pidx), &mut buf)),
+        }
+        buf.truncate(left.min(buf.len() as u64) as usize);
+        left -= buf.len() as u64;
+        sink(&buf)?;
+    }
+    Ok(())
+}
+
+/// Payload bytes `range` (0-based, end exclusive) without the RG peel: byte
+/// `i` is step `i + 1` (for 9-bit symbols, the steps holding bits
+/// `8i..8i + 8`), found by scanning A for the steps in range. The
+/// container is structurally validated and the range must be fully and
+/// uniquely covered, but RG convergence and integrity are not checked; use
+/// `decode_range_with` for that.
+pub fn decode_range(raw: &[u8], range: Range<u64>) -> Result<Vec<u8>> {
+    decode_range_with(raw, range, None)
+}
+
+/// `decode_range`, optionally running the full verified peel with `verify`
+/// first so the bytes are only returned if the whole canvas decodes.
+pub fn decode_range_with(raw: &[u8], range: Range<u64>, verify: Option<&DecodeOptions>) -> Result<Vec<u8>> {
+    let view = match verify {
+        Some(opts) => CanvasView::with_options(raw, opts)?,
+        None => CanvasView::new(raw)?,
+    };
+    let len = view.header().payload_len();
+    let Range { start, end } = range;
+    if start > end || end > len {
+        return Err(CvpError::RangeOutOfBounds { start, end, len });
+    }
+    let alphabet = view.header().alphabet();
+    let (symbols, skip) = alphabet.symbols_for(start..end);
+    let cfg = view.config();
+
+    if let Some(opts) = verify {
+        let step_to_pidx = peel_verified(&view, opts)?;
+        let steps = &step_to_pidx[symbols.start as usize + 1..symbols.end as usize + 1];
+        return Ok(bytes_of_symbols(alphabet, skip, end - start, steps.iter().map(|&pidx| cfg.symbol_of(pidx))));
+    }
+
+    // steps first..=last
+    let (first, last) = (symbols.start + 1, symbols.end);
+    let mut out = vec![0u16; (symbols.end - symbols.start) as usize];
+    let mut seen = vec![false; out.len()];
+    for entry in view.a_entries() {
+        for (page, mask) in entry.pages {
+            let base = page << 6;
+            if base + 63 < first || base > last { continue; }
+            let mut m = mask;
+            while m != 0 {
+                let step = base + m.trailing_zeros() as u64;
+                if (first..=last).contains(&step) {
+                    let i = (step - first) as usize;
+                    if seen[i] { return Err(CvpError::StepCollision { step }); }
+                    seen[i] = true;
+                    out[i] = cfg.symbol_of(entry.pidx);
+                }
+                m &= m - 1;
+            }
+        }
+    }
+    if let Some(i) = seen.iter().position(|&s| !s) {
+        return Err(CvpError::MissingStep { step: first + i as u64 });
+    }
+    Ok(bytes_of_symbols(alphabet, skip, end - start, out))
+}
+
+/// `len` payload bytes from consecutive symbols, dropping the first `skip` bits.
+fn bytes_of_symbols(alphabet: Alphabet, skip: u32, len: u64, symbols: impl IntoIterator<Item = u16>) -> Vec<u8> {
+    let mut out = Vec::with_capacity(len as usize + 2);
+    match alphabet {
+        Alphabet::Byte => out.extend(symbols.into_iter().map(|s| s as u8)),
+        Alphabet::Nine => {
+            let mut unpacker = alphabet::Unpacker::skipping(skip);
+            symbols.into_iter().for_each(|s| unpacker.push(s, &mut out));
+        }
+    }
+    out.truncate(len as usize);
+    out
+}
 
-    let mut out = vec![0u8; n as usize];
+/// Run the full peel and every post-peel check (A empty, RG FULL, integrity)
+/// without materializing the payload. Returns the step->pidx index (entry 0
+/// unused); step `s` decodes to symbol `cfg.symbol_of(index[s])`.
+pub(crate) fn peel_verified(view: &CanvasView, opts: &DecodeOptions) -> Result<Vec<u32>> {
+    peel_staged(view, opts).map_err(|(_, e)| e)
+}
+
+/// `peel_verified`, reporting which stage an error came from.
+pub(crate) fn peel_staged(view: &CanvasView, opts: &DecodeOptions) -> Result<Vec<u32>, (Stage, CvpError)> {
+    let at = |stage| move |e| (stage, e);
+    let Container { header, mut rg, mut a, integrity, .. } = view.to_container().map_err(at(Stage::Parse))?;
+    let n = header.n;
+    let Profile { cfg, baseline, .. } = rg.profile;
+
+    if opts.cross_check {
+        let mismatches = verify_rg_against_a(&rg, &a).map_err(at(Stage::CrossCheck))?;
+        if let Some(m) = mismatches.first() {
+            return Err((Stage::CrossCheck, CvpError::RgInconsistent {
+                lane: m.lane,
+                pidx: m.pidx,
+                expected: m.expected,
+                stored: m.stored,
+                count: mismatches.len(),
+            }));
+        }
+    }
+
+    let step_to_pidx = build_step_index_from_a64(&a, n, cfg).map_err(at(Stage::StepIndex))?;
 
     for step in (1..=n).rev() {
         let pidx_u32 = step_to_pidx[step as usize];
         let pidx = pidx_u32 as usize;
 
-        let x = (pidx_u32 & 511) as u8;
-        out[(step - 1) as usize] = x;
+        a.clear_step(pidx_u32, step).map_err(at(Stage::Peel))?; // A first
 
-        a.clear_step(pidx_u32, step)?; // A first
+        let (lane, k) = rg.lane_k(step);
+        let plane = rg.plane_mut(lane);
+        plane[pidx] = baseline.fill(plane[pidx], lane, k, step, pidx_u32).map_err(at(Stage::Peel))?;
+    }
 
-        let (lane, k) = lane_k(step);
-        if lane == 0 {
-            let v = rg.r[pidx] + k;
-            if v > RG_LIMIT_EXACT { bail!("R overflow decode"); }
-            rg.r[pidx] = v;
-        } else {
-            let v = rg.g[pidx] + k;
-            if v > RG_LIMIT_EXACT { bail!("G overflow decode"); }
-            rg.g[pidx] = v;
+    if !a.is_empty() { return Err((Stage::Convergence, CvpError::ANotEmpty { remaining: a.db.len() })); }
+    let base = baseline.value();
+    for (lane, plane) in rg.planes().enumerate() {
+        if let Some(pidx) = plane.iter().position(|&v| v != base) {
+            let (lane, pidx, value) = (lane as u8, pidx as u32, plane[pidx]);
+            return Err((Stage::Convergence, match baseline {
+                Baseline::Full => CvpError::RgNotFull { lane, pidx, value },
+                Baseline::Zero => CvpError::RgNotZero { lane, pidx, value },
+            }));
         }
     }
 
-    if !a.is_empty() { bail!("A not empty after decode"); }
-    if rg.r.iter().any(|&v| v != RG_LIMIT_EXACT) || rg.g.iter().any(|&v| v != RG_LIMIT_EXACT) {
-        bail!("RG not FULL after decode");
+    if header.alphabet() == Alphabet::Nine {
+        let symbol = cfg.symbol_of(step_to_pidx[n as usize]);
+        if symbol & ((1 << header.pad_bits) - 1) != 0 {
+            return Err((Stage::Convergence, CvpError::PaddingNotZero { symbol, pad_bits: header.pad_bits }));
+        }
     }
 
-    Ok(out)
-}
\ No newline at end of file
+    if let Some(expected) = integrity {
+        let mut hasher = integrity::Hasher::new();
+        payload_chunks(&header, cfg, &step_to_pidx[1..], |chunk| {
+            hasher.update(chunk);
+            Ok(())
+        })
+        .map_err(at(Stage::Integrity))?;
+        let actual = hasher.finish();
+        if actual != expected {
+            return Err((Stage::Integrity, CvpError::IntegrityMismatch {
+                crc32: actual.crc32 != expected.crc32,
+                sha256: actual.sha256 != expected.sha256,
+            }));
+        }
+    }
+
+    Ok(step_to_pidx)
+}
@@ -1,32 +1,590 @@
-use anyhow::Result;
-use clap::{Parser, Subcommand};
+use anyhow::{Context, Result};
+use canvapress::archive;
+use canvapress::chain::{self, ChainOptions};
+use canvapress::stream::{Decoder, Encoder};
+use canvapress::{
+    cvp2, Alphabet, Baseline, CanvasConfig, DecodeOptions, EncodeOptions, Inspection, Lanes, Metadata, Profile,
+    Schedule, RG_LIMIT_EXACT,
+};
+use clap::{Args, Parser, Subcommand};
+use serde_json::json;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Component, Path};
+use std::time::{Duration, UNIX_EPOCH};
 
 #[derive(Parser)]
-#[command(name="canvapress", version, about="Canvapress CVP1 encoder/decoder")]
+#[command(name="canvapress", version, about="Canvapress CVP1/CVP2 encoder/decoder")]
 struct Cli {
     #[command(subcommand)]
     cmd: Cmd,
 }
 
+/// Encoding profile shared by `encode` and `capacity`.
+#[derive(Args)]
+struct ProfileArgs {
+    /// Start lanes at 0 and add k (ZERO baseline) instead of subtracting from FULL
+    #[arg(long)]
+    zero: bool,
+    /// Lane schedule: linear, constant:K, modular:M or hashed:SEED
+    #[arg(long, default_value_t = Schedule::Linear)]
+    schedule: Schedule,
+    /// Add B and alpha planes and spread steps over four lanes
+    #[arg(long)]
+    four_lanes: bool,
+    /// Read the payload as 9-bit symbols (needs a width of at least 512)
+    #[arg(long)]
+    nine_bit: bool,
+    /// Canvas width (power of two, at least 256)
+    #[arg(long, default_value_t = canvapress::W)]
+    width: u32,
+    /// Canvas height (power of two); steps wrap the time axis every HEIGHT bytes
+    #[arg(long, default_value_t = canvapress::H)]
+    height: u32,
+}
+
+impl ProfileArgs {
+    fn options(&self) -> Result<EncodeOptions> {
+        let profile = Profile {
+            cfg: CanvasConfig::new(self.width, self.height)?,
+            baseline: if self.zero { Baseline::Zero } else { Baseline::Full },
+            schedule: self.schedule,
+            lanes: if self.four_lanes { Lanes::Four } else { Lanes::Two },
+        };
+        Ok(EncodeOptions {
+            profile,
+            alphabet: if self.nine_bit { Alphabet::Nine } else { Alphabet::Byte },
+            ..Default::default()
+        })
+    }
+}
+
+/// Decoder checks shared by `decode`, `verify` and `archive extract`.
+#[derive(Args)]
+struct Decoding {
+    /// Check RG planes against A before peeling
+    #[arg(long)]
+    cross_check: bool,
+    /// Largest canvas (W * H) to decode; bigger canvases are refused before allocating planes
+    #[arg(long, default_value_t = canvapress::config::DEFAULT_MAX_DECODE_PIXELS)]
+    max_pixels: u64,
+}
+
+impl Decoding {
+    fn options(&self) -> DecodeOptions {
+        DecodeOptions { cross_check: self.cross_check, max_pixels: self.max_pixels }
+    }
+}
+
 #[derive(Subcommand)]
 enum Cmd {
-    Encode { input: String, output: String },
-    Decode { input: String, output: String },
+    Encode {
+        input: String,
+        output: String,
+        /// Omit the RG planes; the decoder recomputes them from A
+        #[arg(long)]
+        compact: bool,
+        /// Store A with the varint/delta encoding
+        #[arg(long)]
+        varint_a: bool,
+        /// Split the payload across as many canvases as it needs (CVPC chain)
+        #[arg(long)]
+        chain: bool,
+        /// Most steps per canvas when chaining
+        #[arg(long, default_value_t = chain::DEFAULT_MAX_STEPS, requires = "chain")]
+        max_steps: u64,
+        /// Write a metadata section recording the input's file name; without it
+        /// the output depends only on the payload and profile
+        #[arg(long)]
+        metadata: bool,
+        /// Also record the current time in the metadata section
+        #[arg(long, requires = "metadata")]
+        created: bool,
+        /// MIME type to record in the metadata section
+        #[arg(long, requires = "metadata")]
+        mime: Option<String>,
+        /// Extra KEY=VALUE pair to record in the metadata section (repeatable)
+        #[arg(long = "meta", value_parser = parse_pair, requires = "metadata")]
+        meta: Vec<(String, String)>,
+        #[command(flatten)]
+        profile: ProfileArgs,
+    },
+    Decode {
+        input: String,
+        /// Defaults to the file name recorded in the metadata section, which
+        /// must not exist yet
+        output: Option<String>,
+        #[command(flatten)]
+        decoding: Decoding,
+    },
+    /// Report pixels whose RG values disagree with the A bitset
+    Crosscheck {
+        input: String,
+        /// Maximum number of mismatches to print
+        #[arg(long, default_value_t = 20)]
+        limit: usize,
+    },
+    /// Multi-file archives (CVPA)
+    Archive {
+        #[command(subcommand)]
+        op: ArchiveCmd,
+    },
+    /// Show header fields, A statistics and section sizes without decoding
+    Inspect {
+        input: String,
+        /// Print JSON instead of text
+        #[arg(long)]
+        json: bool,
+        /// Also check that the file is in canonical form (unpacks the whole canvas)
+        #[arg(long)]
+        canonical: bool,
+    },
+    /// Check that files decode (CVP1/CVP2, chains and archives) without writing anything
+    Verify {
+        #[arg(required = true)]
+        inputs: Vec<String>,
+        #[command(flatten)]
+        decoding: Decoding,
+    },
+    /// Predict lane load for a payload without encoding it
+    Capacity {
+        input: String,
+        /// Number of most loaded pixels to print
+        #[arg(long, default_value_t = 10)]
+        limit: usize,
+        #[command(flatten)]
+        profile: ProfileArgs,
+    },
+}
+
+#[derive(Subcommand)]
+enum ArchiveCmd {
+    /// Add files and directories (recursively), creating the archive if needed
+    Add {
+        archive: String,
+        #[arg(required = true)]
+        paths: Vec<String>,
+        #[command(flatten)]
+        profile: ProfileArgs,
+    },
+    /// List entries: mode, size, mtime, path
+    List { archive: String },
+    /// Extract the named entries, or all of them, under a directory; existing files are never replaced
+    Extract {
+        archive: String,
+        paths: Vec<String>,
+        /// Directory to extract into
+        #[arg(long, default_value = ".")]
+        to: String,
+        #[command(flatten)]
+        decoding: Decoding,
+    },
+}
+
+fn parse_pair(s: &str) -> Result<(String, String), String> {
+    let (k, v) = s.split_once('=').ok_or_else(|| format!("{:?}: expected KEY=VALUE", s))?;
+    Ok((k.to_string(), v.to_string()))
+}
+
+/// Metadata recorded by `encode --metadata`: the input's file name, now if
+/// `created`, and any flags.
+fn encode_metadata(input: &str, created: bool, mime: Option<String>, meta: Vec<(String, String)>) -> Metadata {
+    Metadata {
+        filename: Path::new(input).file_name().and_then(|n| n.to_str()).map(str::to_string),
+        mime_type: mime,
+        created: created.then(chrono::Utc::now),
+        extra: meta.into_iter().collect(),
+    }
+}
+
+/// Output path from the metadata filename; only its last component is used.
+fn recorded_filename(raw: &[u8]) -> Result<String> {
+    let canvas = if raw.starts_with(chain::MAGIC) { chain::links(raw)?[0].raw } else { raw };
+    let name = canvapress::inspect(canvas)?.metadata.and_then(|m| m.filename);
+    let name = name.context("no output given and no file name recorded in the metadata section")?;
+    match Path::new(&name).file_name().and_then(|n| n.to_str()) {
+        Some(base) => Ok(base.to_string()),
+        None => anyhow::bail!("recorded file name {:?} is not usable as an output path", name),
+    }
+}
+
+/// Create `path` through a temporary file in the same directory, renamed
+/// over `path` only once `write` succeeds, so a failed write leaves `path`
+/// as it was: absent after a failed encode, intact after a failed update.
+fn write_atomic<T>(path: &str, write: impl FnOnce(File) -> Result<T>) -> Result<T> {
+    let path = Path::new(path);
+    let name = path.file_name().with_context(|| format!("{}: not a file path", path.display()))?;
+    let tmp = path.with_file_name(format!(".{}.{}.tmp", name.to_string_lossy(), std::process::id()));
+    let result = File::create(&tmp).map_err(anyhow::Error::from).and_then(write);
+    match result {
+        Ok(value) => {
+            std::fs::rename(&tmp, path)?;
+            Ok(value)
+        }
+        Err(e) => {
+            let _ = std::fs::remove_file(&tmp);
+            Err(e)
+        }
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Names of the set CVP2 feature flags.
+fn flag_names(flags: u32) -> Vec<&'static str> {
+    [
+        (cvp2::FLAG_RG_ELIDED, "rg-elided"),
+        (cvp2::FLAG_A_VARINT, "varint-a"),
+        (cvp2::FLAG_BASELINE_ZERO, "baseline-zero"),
+        (cvp2::FLAG_LANE_SCHEDULE, "lane-schedule"),
+        (cvp2::FLAG_FOUR_LANES, "four-lanes"),
+        (cvp2::FLAG_SYMBOLS_9, "symbols-9"),
+    ]
+    .into_iter()
+    .filter(|&(bit, _)| flags & bit != 0)
+    .map(|(_, name)| name)
+    .collect()
+}
+
+fn baseline_name(b: Baseline) -> &'static str {
+    match b {
+        Baseline::Full => "FULL",
+        Baseline::Zero => "ZERO",
+    }
+}
+
+fn inspection_json(path: &str, i: &Inspection) -> serde_json::Value {
+    let h = &i.header;
+    let metadata = i.metadata.as_ref().map(|m| {
+        json!({
+            "filename": m.filename,
+            "mime_type": m.mime_type,
+            "created": m.created.map(|t| t.to_rfc3339()),
+            "extra": m.extra,
+        })
+    });
+    let sections: Vec<_> =
+        i.sections.iter().map(|s| json!({ "name": s.name, "tag": s.tag, "bytes": s.bytes })).collect();
+    json!({
+        "file": path,
+        "format": i.format,
+        "version": h.version,
+        "flags": flag_names(h.flags),
+        "width": h.profile.cfg.w(),
+        "height": h.profile.cfg.h(),
+        "n": h.n,
+        "rg_limit": h.rg_limit,
+        "baseline": baseline_name(h.profile.baseline),
+        "schedule": h.profile.schedule.to_string(),
+        "lanes": h.profile.lanes.count(),
+        "symbol_bits": h.alphabet().bits(),
+        "pad_bits": h.pad_bits,
+        "payload_len": i.payload_len,
+        "a_entries": i.a_entries,
+        "pixels": i.pixels(),
+        "total_pages": i.total_pages,
+        "file_len": i.file_len,
+        "sections": sections,
+        "canonical": i.canonical,
+        "integrity": i.integrity.map(|t| json!({ "crc32": format!("{:08x}", t.crc32), "sha256": hex(&t.sha256) })),
+        "metadata": metadata,
+    })
+}
+
+fn print_inspection(path: &str, i: &Inspection) {
+    let h = &i.header;
+    let canonical = match i.canonical {
+        Some(true) => ", canonical",
+        Some(false) => ", not canonical",
+        None => "",
+    };
+    println!("{}: {} v{}, {} bytes{}", path, i.format, h.version, i.file_len, canonical);
+    println!(
+        "canvas:    {}x{}, {} lanes, {} baseline, {} schedule, {}-bit symbols",
+        h.profile.cfg.w(),
+        h.profile.cfg.h(),
+        h.profile.lanes.count(),
+        baseline_name(h.profile.baseline),
+        h.profile.schedule,
+        h.alphabet().bits()
+    );
+    println!("flags:     {:#010x} [{}]", h.flags, flag_names(h.flags).join(", "));
+    println!("steps:     N = {}, payload {} bytes", h.n, i.payload_len);
+    println!("A:         {} of {} pixels touched, {} pages", i.a_entries, i.pixels(), i.total_pages);
+    for s in &i.sections {
+        let tag = s.tag.map(|t| format!(" (tag {})", t)).unwrap_or_default();
+        println!("section:   {:<10} {:>12} bytes{}", s.name, s.bytes, tag);
+    }
+    match &i.integrity {
+        Some(t) => println!("integrity: crc32 {:08x}, sha256 {}", t.crc32, hex(&t.sha256)),
+        None => println!("integrity: none"),
+    }
+    if let Some(m) = &i.metadata {
+        if let Some(name) = &m.filename {
+            println!("filename:  {}", name);
+        }
+        if let Some(mime) = &m.mime_type {
+            println!("mime type: {}", mime);
+        }
+        if let Some(t) = &m.created {
+            println!("created:   {}", t.to_rfc3339());
+        }
+        for (k, v) in &m.extra {
+            println!("meta:      {} = {}", k, v);
+        }
+    }
+}
+
+/// Archive path of `path`: its normal components joined with `/`.
+fn entry_path(path: &Path) -> Result<String> {
+    let mut parts = Vec::new();
+    for c in path.components() {
+        match c {
+            Component::CurDir => {}
+            Component::Normal(p) => parts.push(p.to_str().context("archive paths must be UTF-8")?),
+            _ => anyhow::bail!("{}: archive paths must be relative without ..", path.display()),
+        }
+    }
+    Ok(parts.join("/"))
+}
+
+/// Files under `path` (itself if it is a file), sorted, with their mode and mtime.
+fn collect_files(path: &Path, out: &mut Vec<(String, u32, i64, Vec<u8>)>) -> Result<()> {
+    // symlink_metadata, so a link is neither followed out of the tree nor archived as its target
+    let meta = std::fs::symlink_metadata(path).with_context(|| path.display().to_string())?;
+    if meta.file_type().is_symlink() {
+        anyhow::bail!("{}: symbolic links cannot be archived", path.display());
+    }
+    if meta.is_dir() {
+        let mut children: Vec<_> = std::fs::read_dir(path)?.map(|e| e.map(|e| e.path())).collect::<Result<_, _>>()?;
+        children.sort();
+        for child in children {
+            collect_files(&child, out)?;
+        }
+        return Ok(());
+    }
+    #[cfg(unix)]
+    let mode = std::os::unix::fs::PermissionsExt::mode(&meta.permissions()) & 0o7777;
+    #[cfg(not(unix))]
+    let mode = if meta.permissions().readonly() { 0o444 } else { 0o644 };
+    let mtime = match meta.modified()?.duration_since(UNIX_EPOCH) {
+        Ok(d) => d.as_secs() as i64,
+        Err(e) => -(e.duration().as_secs() as i64),
+    };
+    out.push((entry_path(path)?, mode, mtime, std::fs::read(path)?));
+    Ok(())
+}
+
+/// Create `entry` under `dir`. Like decode, extraction never replaces an
+/// existing file, and it never follows a symbolic link already in the tree.
+fn write_entry(dir: &Path, entry: &archive::Entry, data: &[u8]) -> Result<()> {
+    std::fs::create_dir_all(dir)?;
+    let mut dest = dir.to_path_buf();
+    let mut parts = entry.path.split('/').peekable();
+    while let Some(part) = parts.next() {
+        dest.push(part);
+        if parts.peek().is_none() {
+            break;
+        }
+        match std::fs::symlink_metadata(&dest) {
+            Ok(meta) if meta.file_type().is_symlink() => {
+                anyhow::bail!("{}: refusing to extract through a symbolic link", dest.display())
+            }
+            Ok(_) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => std::fs::create_dir(&dest)?,
+            Err(e) => return Err(e.into()),
+        }
+    }
+    let file = OpenOptions::new().write(true).create_new(true).open(&dest);
+    let mut file = file.with_context(|| format!("{}: extracting", dest.display()))?;
+    file.write_all(data)?;
+    let mtime = Duration::from_secs(entry.mtime.unsigned_abs());
+    let mtime = if entry.mtime < 0 { UNIX_EPOCH.checked_sub(mtime) } else { UNIX_EPOCH.checked_add(mtime) };
+    if let Some(mtime) = mtime {
+        file.set_modified(mtime)?;
+    }
+    // permission bits only: setuid, setgid and sticky from an archive are dropped
+    #[cfg(unix)]
+    std::fs::set_permissions(&dest, std::os::unix::fs::PermissionsExt::from_mode(entry.mode & 0o777))?;
+    Ok(())
 }
 
 fn main() -> Result<()> {
     let cli = Cli::parse();
     match cli.cmd {
-        Cmd::Encode { input, output } => {
-            let data = std::fs::read(input)?;
-            let raw = canvapress::encode_erase(&data)?;
-            std::fs::write(output, raw)?;
+        Cmd::Encode { input, output, compact, varint_a, chain, max_steps, metadata, created, mime, meta, profile } => {
+            let metadata = metadata.then(|| encode_metadata(&input, created, mime, meta));
+            let opts = EncodeOptions { rg_elided: compact, a_varint: varint_a, metadata, ..profile.options()? };
+            let Profile { lanes, cfg, .. } = opts.profile;
+            if chain {
+                let raw = chain::encode_with(&std::fs::read(input)?, &ChainOptions { encode: opts, max_steps })?;
+                println!("chain: {} canvases, {} bytes", chain::links(&raw)?.len(), raw.len());
+                write_atomic(&output, |mut out| Ok(out.write_all(&raw)?))?;
+                return Ok(());
+            }
+            let input = File::open(input)?;
+            let written = write_atomic(&output, |out| Ok(Encoder::encode(opts, input, out)?))?;
+            if compact {
+                let rg_section = (cvp2::SECTION_HEADER_LEN + lanes.count() * cfg.pixels() * 8) as u64;
+                println!(
+                    "compact: {} bytes (saved {} bytes vs {} with RG planes)",
+                    written,
+                    rg_section,
+                    written + rg_section
+                );
+            }
         }
-        Cmd::Decode { input, output } => {
+        Cmd::Decode { input, output, decoding } => {
             let raw = std::fs::read(input)?;
-            let data = canvapress::decode_fill(&raw)?;
-            std::fs::write(output, data)?;
+            let (output, recorded) = match output {
+                Some(output) => (output, false),
+                None => (recorded_filename(&raw)?, true),
+            };
+            // A name taken from the container never replaces an existing file.
+            let create = |path: &str| -> Result<File> {
+                if recorded {
+                    let file = OpenOptions::new().write(true).create_new(true).open(path);
+                    Ok(file.with_context(|| format!("{}: creating the file named by the container", path))?)
+                } else {
+                    Ok(File::create(path)?)
+                }
+            };
+            // Verify before creating the output so a bad container leaves no file.
+            if raw.starts_with(chain::MAGIC) {
+                let payload = chain::decode_with(&raw, &decoding.options())?;
+                create(&output)?.write_all(&payload)?;
+                return Ok(());
+            }
+            let dec = Decoder::new(&raw, &decoding.options())?;
+            dec.write_to(create(&output)?)?;
+        }
+        Cmd::Crosscheck { input, limit } => {
+            let raw = std::fs::read(&input)?;
+            let c = canvapress::unpack(&raw)?;
+            let mismatches = canvapress::verify_rg_against_a(&c.rg, &c.a)?;
+            for m in mismatches.iter().take(limit) {
+                let (x, y) = c.rg.profile.cfg.xy(m.pidx);
+                println!(
+                    "pidx {} (x={}, y={}) {}: expected {} stored {}",
+                    m.pidx, x, y, canvapress::lane_name(m.lane), m.expected, m.stored
+                );
+            }
+            if mismatches.is_empty() {
+                println!("{}: RG consistent with A", input);
+            } else {
+                anyhow::bail!("{}: {} inconsistent lanes", input, mismatches.len());
+            }
+        }
+        Cmd::Archive { op: ArchiveCmd::Add { archive: path, paths, profile } } => {
+            let mut files = Vec::new();
+            for p in &paths {
+                collect_files(Path::new(p), &mut files)?;
+            }
+            let new: Vec<archive::NewEntry> = files
+                .iter()
+                .map(|(path, mode, mtime, data)| {
+                    archive::NewEntry { path: path.clone(), mode: *mode, mtime: *mtime, data }
+                })
+                .collect();
+            let opts = ChainOptions { encode: profile.options()?, ..Default::default() };
+            let raw = match std::fs::read(&path) {
+                Ok(raw) => archive::add(&raw, &new, &opts)?,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => archive::create(&new, &opts)?,
+                Err(e) => return Err(e.into()),
+            };
+            write_atomic(&path, |mut out| Ok(out.write_all(&raw)?))?;
+            println!("{}: added {} entries", path, new.len());
+        }
+        Cmd::Archive { op: ArchiveCmd::List { archive: path } } => {
+            for e in archive::list(&std::fs::read(path)?)? {
+                println!("{:o} {:>12} {:>12} {}", e.mode, e.size, e.mtime, e.path);
+            }
+        }
+        Cmd::Archive { op: ArchiveCmd::Extract { archive: path, paths, to, decoding } } => {
+            let raw = std::fs::read(path)?;
+            let opts = decoding.options();
+            // Decode everything requested before writing any file.
+            let entries = if paths.is_empty() {
+                archive::extract_all(&raw, &opts)?
+            } else {
+                let index = archive::list(&raw)?;
+                let mut out = Vec::new();
+                for p in &paths {
+                    let data = archive::extract(&raw, p, &opts)?;
+                    out.push((index.iter().find(|e| &e.path == p).unwrap().clone(), data));
+                }
+                out
+            };
+            for (entry, data) in &entries {
+                write_entry(Path::new(&to), entry, data)?;
+            }
+        }
+        Cmd::Inspect { input, json, canonical } => {
+            let info = canvapress::inspect_with(&std::fs::read(&input)?, canonical)?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&inspection_json(&input, &info))?);
+            } else {
+                print_inspection(&input, &info);
+            }
+        }
+        Cmd::Verify { inputs, decoding } => {
+            let opts = decoding.options();
+            let mut failed = 0;
+            for input in &inputs {
+                let result = std::fs::read(input).map_err(anyhow::Error::from).and_then(|raw| {
+                    Ok(canvapress::verify(&raw, &opts)?)
+                });
+                match result {
+                    Ok(v) => println!(
+                        "{}: ok ({}, {} canvases, {} steps, {} bytes{})",
+                        input,
+                        v.format,
+                        v.canvases,
+                        v.steps,
+                        v.payload_len,
+                        if v.integrity { ", integrity checked" } else { "" }
+                    ),
+                    Err(e) => {
+                        failed += 1;
+                        println!("{}: FAIL {}", input, e);
+                    }
+                }
+            }
+            if failed > 0 {
+                anyhow::bail!("{} of {} files failed verification", failed, inputs.len());
+            }
+        }
+        Cmd::Capacity { input, limit, profile } => {
+            let payload = std::fs::read(&input)?;
+            let report = canvapress::capacity_report_with(&payload, &profile.options()?)?;
+            println!(
+                "{}: {} bytes, {} steps on {}x{} over {} lanes",
+                input,
+                payload.len(),
+                report.n,
+                report.cfg.w(),
+                report.cfg.h(),
+                report.lanes.count()
+            );
+            let percent = report.max_load as f64 / RG_LIMIT_EXACT as f64 * 100.0;
+            println!("max lane load: {} of {} ({:.3}%)", report.max_load, RG_LIMIT_EXACT, percent);
+            for p in report.worst(limit) {
+                let (x, y) = report.cfg.xy(p.pidx);
+                println!("pidx {} (x={}, y={}) {}: load {}", p.pidx, x, y, canvapress::lane_name(p.lane), p.load);
+            }
+            match report.headroom() {
+                Some(headroom) => println!("headroom: {}; max safe N: {} (whole payload)", headroom, report.max_safe_n),
+                None => anyhow::bail!(
+                    "{}: a lane passes RG_LIMIT at step {}; max safe N: {} of {}",
+                    input,
+                    report.max_safe_n + 1,
+                    report.max_safe_n,
+                    report.n
+                ),
+            }
         }
     }
     Ok(())
-}
\ No newline at end of file
+}
@@ -0,0 +1,112 @@
+//! Optional metadata section (SEC_METADATA): what the payload was.
+//!
+//! ```text
+//! records until the end of the section: kind u8, len varint, body[len]
+//!   1  filename   UTF-8
+//!   2  MIME type  UTF-8
+//!   3  created    i64 seconds since the Unix epoch, u32 nanoseconds (UTC)
+//!   4  key/value  key_len varint, key UTF-8, value UTF-8 (rest of the body)
+//! ```
+//!
+//! Kinds 1-3 appear at most once and keys are unique. Unknown kinds are
+//! skipped. The section is informational: it is not covered by the
+//! integrity trailer and the decoder ignores it.
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+
+use crate::sections::{malformed, write_varint, Reader};
+use crate::Result;
+
+const KIND_FILENAME: u8 = 1;
+const KIND_MIME_TYPE: u8 = 2;
+const KIND_CREATED: u8 = 3;
+const KIND_PAIR: u8 = 4;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Metadata {
+    /// Original file name.
+    pub filename: Option<String>,
+    pub mime_type: Option<String>,
+    pub created: Option<DateTime<Utc>>,
+    /// Free-form key/value pairs.
+    pub extra: BTreeMap<String, String>,
+}
+
+impl Metadata {
+    pub fn is_empty(&self) -> bool {
+        *self == Metadata::default()
+    }
+
+    /// Section body, records in kind order then key order.
+    pub(crate) fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        let mut record = |kind: u8, body: &[u8]| {
+            out.push(kind);
+            write_varint(&mut out, body.len() as u64).unwrap();
+            out.extend_from_slice(body);
+        };
+        if let Some(name) = &self.filename {
+            record(KIND_FILENAME, name.as_bytes());
+        }
+        if let Some(mime) = &self.mime_type {
+            record(KIND_MIME_TYPE, mime.as_bytes());
+        }
+        if let Some(t) = &self.created {
+            let mut body = t.timestamp().to_le_bytes().to_vec();
+            body.extend_from_slice(&t.timestamp_subsec_nanos().to_le_bytes());
+            record(KIND_CREATED, &body);
+        }
+        for (key, value) in &self.extra {
+            let mut body = Vec::with_capacity(key.len() + value.len() + 2);
+            write_varint(&mut body, key.len() as u64).unwrap();
+            body.extend_from_slice(key.as_bytes());
+            body.extend_from_slice(value.as_bytes());
+            record(KIND_PAIR, &body);
+        }
+        out
+    }
+
+    /// Parse a section body; `rd` spans exactly the body.
+    pub(crate) fn read(rd: &mut Reader) -> Result<Self> {
+        let mut meta = Metadata::default();
+        while rd.remaining() > 0 {
+            let at = rd.off;
+            let kind = rd.u8("metadata kind")?;
+            let len = rd.varint("metadata len")?;
+            if len > rd.remaining() as u64 {
+                return Err(malformed(at, "metadata len", len, "exceeds section"));
+            }
+            let body_at = rd.off;
+            let mut body = rd.sub(len as usize, "metadata record")?;
+            let text = |bytes: &[u8], field| {
+                String::from_utf8(bytes.to_vec()).map_err(|_| malformed(body_at, field, len, "not UTF-8"))
+            };
+            let repeated = match kind {
+                KIND_FILENAME => meta.filename.replace(text(body.rest(), "filename")?).is_some(),
+                KIND_MIME_TYPE => meta.mime_type.replace(text(body.rest(), "MIME type")?).is_some(),
+                KIND_CREATED => {
+                    let (secs, nanos) = (body.u64("created")? as i64, body.u32("created nanos")?);
+                    let t = DateTime::from_timestamp(secs, nanos)
+                        .filter(|_| body.remaining() == 0)
+                        .ok_or(malformed(body_at, "created", secs as u64, "not a valid time"))?;
+                    meta.created.replace(t).is_some()
+                }
+                KIND_PAIR => {
+                    let key_len = body.varint("key len")?;
+                    if key_len > body.remaining() as u64 {
+                        return Err(malformed(body_at, "key len", key_len, "exceeds record"));
+                    }
+                    let key = text(body.bytes(key_len as usize, "key")?, "key")?;
+                    meta.extra.insert(key, text(body.rest(), "value")?).is_some()
+                }
+                _ => false,
+            };
+            if repeated {
+                return Err(malformed(at, "metadata kind", kind as u64, "repeated"));
+            }
+        }
+        Ok(meta)
+    }
+}
@@ -0,0 +1,193 @@
+//! Lane schedules: which lane a step erases and by how much.
+//!
+//! The schedule bounds capacity: a pixel lane overflows once the `k` of its
+//! steps sum past RG_LIMIT. `Linear` is the original `lane_k`; the others
+//! trade the strength of the RG cross-check for headroom on long payloads.
+//! Non-linear schedules are recorded in the CVP2 header (FLAG_LANE_SCHEDULE).
+//!
+//! Any schedule can also be spread over four lanes (R, G, B, alpha) with
+//! `FourLane`, which halves the number of steps each plane absorbs
+//! (FLAG_FOUR_LANES).
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::{CvpError, Result, RG_LIMIT_EXACT};
+
+pub trait LaneSchedule {
+    /// Lane (0=R, 1=G, 2=B, 3=alpha) and amount `k` for `step` (1-based).
+    fn lane_k(&self, step: u64) -> (u8, u64);
+}
+
+/// Odd steps to R with k=(step+1)/2, even steps to G with k=step/2.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Linear;
+
+impl LaneSchedule for Linear {
+    #[inline]
+    fn lane_k(&self, step: u64) -> (u8, u64) {
+        crate::lane_k(step)
+    }
+}
+
+/// Odd steps to R, even to G, every step erasing the same `k`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ConstantK(pub u64);
+
+impl LaneSchedule for ConstantK {
+    #[inline]
+    fn lane_k(&self, step: u64) -> (u8, u64) {
+        ((step & 1 == 0) as u8, self.0)
+    }
+}
+
+/// Odd steps to R, even to G, with k = (step mod m) + 1.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ModularK(pub u64);
+
+impl LaneSchedule for ModularK {
+    #[inline]
+    fn lane_k(&self, step: u64) -> (u8, u64) {
+        ((step & 1 == 0) as u8, step % self.0 + 1)
+    }
+}
+
+/// Lane picked by a seeded hash of the step, k = ceil(step / 2) as in `Linear`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HashedLane(pub u64);
+
+impl LaneSchedule for HashedLane {
+    #[inline]
+    fn lane_k(&self, step: u64) -> (u8, u64) {
+        // splitmix64 finalizer
+        let mut z = (step ^ self.0).wrapping_add(0x9e37_79b9_7f4a_7c15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^= z >> 31;
+        ((z & 1) as u8, (step >> 1) + (step & 1))
+    }
+}
+
+/// Spread a two-lane schedule over R, G, B and alpha: step pairs alternate
+/// between R/G and B/alpha, so each plane takes every fourth step of a linear
+/// schedule instead of every second.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FourLane<S>(pub S);
+
+impl<S: LaneSchedule> LaneSchedule for FourLane<S> {
+    #[inline]
+    fn lane_k(&self, step: u64) -> (u8, u64) {
+        let (lane, k) = self.0.lane_k(step);
+        (lane + 2 * ((step.wrapping_sub(1) >> 1) & 1) as u8, k)
+    }
+}
+
+/// Number of RG planes: R and G, or R, G, B and alpha.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Lanes {
+    #[default]
+    Two,
+    Four,
+}
+
+impl Lanes {
+    pub fn count(self) -> usize {
+        match self {
+            Lanes::Two => 2,
+            Lanes::Four => 4,
+        }
+    }
+
+    /// `schedule` as laid out over these lanes.
+    #[inline]
+    pub fn lane_k(self, schedule: &Schedule, step: u64) -> (u8, u64) {
+        match self {
+            Lanes::Two => schedule.lane_k(step),
+            Lanes::Four => FourLane(*schedule).lane_k(step),
+        }
+    }
+}
+
+/// The schedules a container can name: id u8 plus one u64 parameter.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Schedule {
+    #[default]
+    Linear,
+    ConstantK(u64),
+    ModularK(u64),
+    HashedLane(u64),
+}
+
+impl Schedule {
+    /// Rebuild a schedule from its header fields; parameters must keep every
+    /// `k` in 1..=RG_LIMIT.
+    pub fn from_id(id: u8, param: u64) -> Result<Self> {
+        let s = match id {
+            0 if param == 0 => Schedule::Linear,
+            1 if (1..=RG_LIMIT_EXACT).contains(&param) => Schedule::ConstantK(param),
+            2 if (1..=RG_LIMIT_EXACT).contains(&param) => Schedule::ModularK(param),
+            3 => Schedule::HashedLane(param),
+            _ => return Err(CvpError::UnsupportedSchedule { id, param }),
+        };
+        Ok(s)
+    }
+
+    pub fn id(&self) -> u8 {
+        match self {
+            Schedule::Linear => 0,
+            Schedule::ConstantK(_) => 1,
+            Schedule::ModularK(_) => 2,
+            Schedule::HashedLane(_) => 3,
+        }
+    }
+
+    pub fn param(&self) -> u64 {
+        match *self {
+            Schedule::Linear => 0,
+            Schedule::ConstantK(p) | Schedule::ModularK(p) | Schedule::HashedLane(p) => p,
+        }
+    }
+}
+
+impl LaneSchedule for Schedule {
+    #[inline]
+    fn lane_k(&self, step: u64) -> (u8, u64) {
+        match *self {
+            Schedule::Linear => Linear.lane_k(step),
+            Schedule::ConstantK(k) => ConstantK(k).lane_k(step),
+            Schedule::ModularK(m) => ModularK(m).lane_k(step),
+            Schedule::HashedLane(seed) => HashedLane(seed).lane_k(step),
+        }
+    }
+}
+
+impl fmt::Display for Schedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Schedule::Linear => write!(f, "linear"),
+            Schedule::ConstantK(k) => write!(f, "constant:{}", k),
+            Schedule::ModularK(m) => write!(f, "modular:{}", m),
+            Schedule::HashedLane(seed) => write!(f, "hashed:{}", seed),
+        }
+    }
+}
+
+/// Parses the `Display` form: `linear`, `constant:K`, `modular:M`, `hashed:SEED`.
+impl FromStr for Schedule {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, String> {
+        let (name, param) = match s.split_once(':') {
+            Some((name, p)) => (name, Some(p.parse::<u64>().map_err(|e| format!("{}: {}", s, e))?)),
+            None => (s, None),
+        };
+        let id = match (name, param) {
+            ("linear", None) => return Ok(Schedule::Linear),
+            ("constant", Some(_)) => 1,
+            ("modular", Some(_)) => 2,
+            ("hashed", Some(_)) => 3,
+            _ => return Err(format!("unknown schedule {:?} (linear, constant:K, modular:M, hashed:SEED)", s)),
+        };
+        Schedule::from_id(id, param.unwrap()).map_err(|e| e.to_string())
+    }
+}
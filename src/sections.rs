@@ -0,0 +1,439 @@
+//! Shared codecs for the RG planes and the A bitset, used by CVP1 and CVP2.
+
+use std::collections::HashSet;
+use std::io::{self, Write};
+
+use crate::{ABitset, Alphabet, CanvasConfig, CvpError, RGCanvas, Result, RG_LIMIT_EXACT};
+
+pub(crate) fn malformed(offset: usize, field: &'static str, value: u64, reason: &'static str) -> CvpError {
+    CvpError::Malformed { offset, field, value, reason }
+}
+
+/// Little-endian cursor over RAW bytes; every read is bounds-checked.
+/// Offsets are absolute, so a reader over `&raw[..end]` still reports
+/// positions in the whole file.
+#[derive(Clone)]
+pub(crate) struct Reader<'a> {
+    buf: &'a [u8],
+    pub(crate) off: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub(crate) fn new(buf: &'a [u8], off: usize) -> Self {
+        Self { buf, off }
+    }
+
+    #[inline]
+    pub(crate) fn remaining(&self) -> usize {
+        self.buf.len() - self.off
+    }
+
+    #[inline]
+    fn take<const N: usize>(&mut self, field: &'static str) -> Result<[u8; N]> {
+        if self.remaining() < N {
+            return Err(CvpError::Truncated { offset: self.off, field });
+        }
+        let v = self.buf[self.off..self.off + N].try_into().unwrap();
+        self.off += N;
+        Ok(v)
+    }
+
+    pub(crate) fn bytes(&mut self, len: usize, field: &'static str) -> Result<&'a [u8]> {
+        if self.remaining() < len {
+            return Err(CvpError::Truncated { offset: self.off, field });
+        }
+        let v = &self.buf[self.off..self.off + len];
+        self.off += len;
+        Ok(v)
+    }
+
+    /// Everything not yet read.
+    pub(crate) fn rest(&self) -> &'a [u8] {
+        &self.buf[self.off..]
+    }
+
+    /// Reader over the next `len` bytes, keeping absolute offsets; skips them here.
+    pub(crate) fn sub(&mut self, len: usize, field: &'static str) -> Result<Reader<'a>> {
+        let at = self.off;
+        self.bytes(len, field)?;
+        Ok(Reader { buf: &self.buf[..at + len], off: at })
+    }
+
+    pub(crate) fn u8(&mut self, field: &'static str) -> Result<u8> {
+        self.take::<1>(field).map(|b| b[0])
+    }
+
+    pub(crate) fn u16(&mut self, field: &'static str) -> Result<u16> {
+        self.take(field).map(u16::from_le_bytes)
+    }
+
+    pub(crate) fn u32(&mut self, field: &'static str) -> Result<u32> {
+        self.take(field).map(u32::from_le_bytes)
+    }
+
+    pub(crate) fn u64(&mut self, field: &'static str) -> Result<u64> {
+        self.take(field).map(u64::from_le_bytes)
+    }
+
+    /// Unsigned LEB128, at most 10 bytes; overlong or overflowing values are rejected.
+    pub(crate) fn varint(&mut self, field: &'static str) -> Result<u64> {
+        let at = self.off;
+        let mut v: u64 = 0;
+        for i in 0..10 {
+            let b = self.u8(field)?;
+            let bits = (b & 0x7f) as u64;
+            if i == 9 && bits > 1 {
+                return Err(malformed(at, field, bits, "varint overflows u64"));
+            }
+            v |= bits << (7 * i);
+            if b & 0x80 == 0 {
+                if b == 0 && i > 0 {
+                    return Err(malformed(at, field, v, "overlong varint"));
+                }
+                return Ok(v);
+            }
+        }
+        Err(malformed(at, field, v, "varint longer than 10 bytes"))
+    }
+}
+
+/// Write sink that only counts bytes, used to size a section before streaming it.
+#[derive(Default)]
+pub(crate) struct Counter(pub(crate) u64);
+
+impl Write for Counter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0 += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+pub(crate) fn write_varint<W: Write>(out: &mut W, mut v: u64) -> Result<()> {
+    let mut buf = [0u8; 10];
+    let mut i = 0;
+    while v >= 0x80 {
+        buf[i] = (v as u8) | 0x80;
+        v >>= 7;
+        i += 1;
+    }
+    buf[i] = v as u8;
+    out.write_all(&buf[..=i])?;
+    Ok(())
+}
+
+/// R plane then G plane (then B and alpha on four-lane canvases), W * H u64 each.
+pub(crate) fn write_planes<W: Write>(out: &mut W, rg: &RGCanvas) -> Result<()> {
+    let mut buf = [0u8; 8 * 512];
+    for plane in rg.planes() {
+        for chunk in plane.chunks(512) {
+            for (b, v) in buf.chunks_exact_mut(8).zip(chunk) {
+                b.copy_from_slice(&v.to_le_bytes());
+            }
+            out.write_all(&buf[..chunk.len() * 8])?;
+        }
+    }
+    Ok(())
+}
+
+/// Validate `lanes` planes in place and return their bytes without copying.
+pub(crate) fn check_planes<'a>(rd: &mut Reader<'a>, pixels: usize, lanes: usize) -> Result<&'a [u8]> {
+    if rd.remaining() < lanes * pixels * 8 {
+        return Err(CvpError::Truncated { offset: rd.off + rd.remaining(), field: "RG planes" });
+    }
+    let start = rd.off;
+    let bytes = rd.bytes(lanes * pixels * 8, "RG planes")?;
+    for (i, b) in bytes.chunks_exact(8).enumerate() {
+        let v = u64::from_le_bytes(b.try_into().unwrap());
+        if v > RG_LIMIT_EXACT {
+            return Err(malformed(start + i * 8, "RG value", v, "exceeds RG_LIMIT"));
+        }
+    }
+    Ok(bytes)
+}
+
+/// Lane value `i` of validated plane bytes (R plane first, then G, B, alpha).
+#[inline]
+pub(crate) fn plane_value(planes: &[u8], i: usize) -> u64 {
+    u64::from_le_bytes(planes[i * 8..i * 8 + 8].try_into().unwrap())
+}
+
+/// True if `a` fits the plain layout: page_count is u16 and pages are u32.
+pub(crate) fn plain_a_fits(a: &ABitset) -> bool {
+    a.db.values().all(|pages| pages.len() <= u16::MAX as usize && pages.keys().all(|&p| p <= u32::MAX as u64))
+}
+
+/// entry_count, then per entry (sorted by pidx): pidx, page_count, pages
+/// sorted by page as (page, mask). page_count is u16 and pages are u32 in
+/// this layout; `format` names the container in the `FormatLimit` error.
+pub(crate) fn write_a<W: Write>(out: &mut W, a: &ABitset, format: &'static str) -> Result<()> {
+    let entry_count = a.db.len() as u32;
+    out.write_all(&entry_count.to_le_bytes())?;
+
+    let mut pidxs: Vec<u32> = a.db.keys().copied().collect();
+    pidxs.sort_unstable();
+    for pidx in pidxs {
+        let pages = &a.db[&pidx];
+        out.write_all(&pidx.to_le_bytes())?;
+        let pcnt = u16::try_from(pages.len()).map_err(|_| CvpError::FormatLimit {
+            format,
+            field: "page_count",
+            value: pages.len() as u64,
+            max: u16::MAX as u64,
+        })?;
+        out.write_all(&pcnt.to_le_bytes())?;
+        let mut page_list: Vec<(u64, u64)> = pages.iter().map(|(&p, &m)| (p, m)).collect();
+        page_list.sort_unstable_by_key(|&(p, _)| p);
+        for (page, mask) in page_list {
+            let page = u32::try_from(page)
+                .map_err(|_| CvpError::FormatLimit { format, field: "page", value: page, max: u32::MAX as u64 })?;
+            out.write_all(&page.to_le_bytes())?;
+            out.write_all(&mask.to_le_bytes())?;
+        }
+    }
+    Ok(())
+}
+
+/// Structural checks shared by both A encodings. Besides the bounds, every
+/// step must sit where an encoder could have put it: in a column of the
+/// alphabet, on the row `step & time_mask` of its pixel.
+struct AValidator {
+    n: u64,
+    cfg: CanvasConfig,
+    /// Columns the alphabet can touch.
+    columns: u32,
+    last_page: u64,
+    seen_pidx: Vec<bool>,
+    entry_pages: HashSet<u64>,
+    /// Row of the current entry's pixel.
+    row: u64,
+    /// Bits of a page that fall on `row`.
+    row_bits: u64,
+    total_bits: u64,
+}
+
+impl AValidator {
+    fn new(n: u64, cfg: CanvasConfig, alphabet: Alphabet) -> Self {
+        Self {
+            n,
+            cfg,
+            columns: 1 << alphabet.bits(),
+            last_page: n >> 6,
+            seen_pidx: vec![false; cfg.pixels()],
+            entry_pages: HashSet::new(),
+            row: 0,
+            row_bits: 0,
+            total_bits: 0,
+        }
+    }
+
+    fn pidx(&mut self, at: usize, pidx: u64) -> Result<u32> {
+        if pidx >= self.cfg.pixels() as u64 {
+            return Err(malformed(at, "pidx", pidx, "out of range"));
+        }
+        let (x, y) = self.cfg.xy(pidx as u32);
+        if x >= self.columns {
+            return Err(malformed(at, "pidx", pidx, "column outside the alphabet"));
+        }
+        if self.seen_pidx[pidx as usize] {
+            return Err(malformed(at, "pidx", pidx, "duplicated"));
+        }
+        self.seen_pidx[pidx as usize] = true;
+        self.entry_pages.clear();
+        // a page covers 64 steps: with H >= 64 one bit of the right pages is
+        // on the row, with H < 64 every H-th bit of every page
+        let h = self.cfg.h() as u64;
+        self.row = y as u64;
+        self.row_bits = (0..64).step_by(h.min(64) as usize).fold(0, |m, b| m | 1 << (b + (self.row & 63)));
+        Ok(pidx as u32)
+    }
+
+    fn page(&mut self, page_at: usize, page: u64, mask_at: usize, mask: u64) -> Result<()> {
+        if page > self.last_page {
+            return Err(malformed(page_at, "page", page, "beyond N"));
+        }
+        if mask == 0 {
+            return Err(malformed(mask_at, "mask", 0, "empty page"));
+        }
+        // steps must lie in 1..=N
+        let mut valid = u64::MAX;
+        if page == 0 { valid &= !1; }
+        if page == self.last_page { valid &= u64::MAX >> (63 - (self.n & 63)); }
+        if mask & !valid != 0 {
+            return Err(malformed(mask_at, "mask", mask, "steps outside 1..=N"));
+        }
+        let tm = self.cfg.time_mask();
+        if (page << 6) & tm != self.row & tm & !63 || mask & !self.row_bits != 0 {
+            return Err(malformed(mask_at, "mask", mask, "steps off the pixel's row"));
+        }
+        if !self.entry_pages.insert(page) {
+            return Err(malformed(page_at, "page", page, "duplicated"));
+        }
+        self.total_bits += mask.count_ones() as u64;
+        Ok(())
+    }
+
+    fn finish(self) -> Result<()> {
+        if self.total_bits != self.n {
+            return Err(CvpError::StepCountMismatch { bits: self.total_bits, n: self.n });
+        }
+        Ok(())
+    }
+}
+
+/// Validate a plain A section against N, calling `visit(pidx, page, mask)`
+/// for every page in file order once it has passed the structural checks.
+/// The reader must end where the section ends; trailing bytes are left for
+/// the caller to reject.
+pub(crate) fn walk_a<F: FnMut(u32, u64, u64)>(
+    rd: &mut Reader,
+    n: u64,
+    cfg: CanvasConfig,
+    alphabet: Alphabet,
+    mut visit: F,
+) -> Result<()> {
+    let pixels = cfg.pixels();
+    // smallest valid entry: pidx(4) + page_count(2) + one page(12)
+    const MIN_ENTRY: usize = 4 + 2 + 12;
+    let at = rd.off;
+    let entry_count = rd.u32("entry_count")?;
+    if entry_count as usize > pixels || entry_count as usize > rd.remaining() / MIN_ENTRY {
+        return Err(malformed(at, "entry_count", entry_count as u64, "exceeds pixels or remaining bytes"));
+    }
+
+    let mut check = AValidator::new(n, cfg, alphabet);
+
+    for _ in 0..entry_count {
+        let at = rd.off;
+        let pidx = check.pidx(at, rd.u32("pidx")? as u64)?;
+
+        let at = rd.off;
+        let pcnt = rd.u16("page_count")? as usize;
+        if pcnt == 0 || pcnt > rd.remaining() / 12 {
+            return Err(malformed(at, "page_count", pcnt as u64, "zero or exceeds remaining bytes"));
+        }
+
+        for _ in 0..pcnt {
+            let page_at = rd.off;
+            let page = rd.u32("page")?;
+            let mask_at = rd.off;
+            let mask = rd.u64("mask")?;
+            check.page(page_at, page as u64, mask_at, mask)?;
+            visit(pidx, page as u64, mask);
+        }
+    }
+
+    check.finish()
+}
+
+/// Compact A encoding (FLAG_A_VARINT). All integers are LEB128 varints:
+///
+/// ```text
+/// entry_count
+/// per entry, ascending pidx:
+///   pidx delta (first entry: pidx itself), page_count
+///   per page, ascending:
+///     (page delta << 1) | single     first page: page itself
+///     single ? bit index u8 : mask u64 LE
+/// ```
+///
+/// Single-bit masks, the common case for random payloads, cost one byte.
+pub(crate) fn write_a_varint<W: Write>(out: &mut W, a: &ABitset) -> Result<()> {
+    write_varint(out, a.db.len() as u64)?;
+
+    let mut pidxs: Vec<u32> = a.db.keys().copied().collect();
+    pidxs.sort_unstable();
+    let mut prev_pidx = 0;
+    for (i, pidx) in pidxs.into_iter().enumerate() {
+        let pages = &a.db[&pidx];
+        write_varint(out, if i == 0 { pidx } else { pidx - prev_pidx } as u64)?;
+        prev_pidx = pidx;
+        write_varint(out, pages.len() as u64)?;
+
+        let mut page_list: Vec<(u64, u64)> = pages.iter().map(|(&p, &m)| (p, m)).collect();
+        page_list.sort_unstable_by_key(|&(p, _)| p);
+        let mut prev_page = 0;
+        for (j, (page, mask)) in page_list.into_iter().enumerate() {
+            let delta = if j == 0 { page } else { page - prev_page };
+            prev_page = page;
+            let single = mask.count_ones() == 1;
+            write_varint(out, (delta << 1) | single as u64)?;
+            if single {
+                out.write_all(&[mask.trailing_zeros() as u8])?;
+            } else {
+                out.write_all(&mask.to_le_bytes())?;
+            }
+        }
+    }
+    Ok(())
+}
+
+pub(crate) fn walk_a_varint<F: FnMut(u32, u64, u64)>(
+    rd: &mut Reader,
+    n: u64,
+    cfg: CanvasConfig,
+    alphabet: Alphabet,
+    mut visit: F,
+) -> Result<()> {
+    let pixels = cfg.pixels();
+    // smallest valid entry: pidx(1) + page_count(1) + one single-bit page(2)
+    const MIN_ENTRY: usize = 4;
+    let at = rd.off;
+    let entry_count = rd.varint("entry_count")?;
+    if entry_count > pixels as u64 || entry_count > (rd.remaining() / MIN_ENTRY) as u64 {
+        return Err(malformed(at, "entry_count", entry_count, "exceeds pixels or remaining bytes"));
+    }
+
+    let mut check = AValidator::new(n, cfg, alphabet);
+    let mut prev_pidx: u64 = 0;
+
+    for i in 0..entry_count {
+        let at = rd.off;
+        let delta = rd.varint("pidx delta")?;
+        if i > 0 && delta == 0 {
+            return Err(malformed(at, "pidx delta", 0, "entries not strictly ascending"));
+        }
+        let pidx = check.pidx(at, prev_pidx.saturating_add(delta))?;
+        prev_pidx = pidx as u64;
+
+        let at = rd.off;
+        let pcnt = rd.varint("page_count")?;
+        if pcnt == 0 || pcnt > (rd.remaining() / 2) as u64 {
+            return Err(malformed(at, "page_count", pcnt, "zero or exceeds remaining bytes"));
+        }
+
+        let mut prev_page: u64 = 0;
+        for j in 0..pcnt {
+            let page_at = rd.off;
+            let head = rd.varint("page delta")?;
+            let delta = head >> 1;
+            if j > 0 && delta == 0 {
+                return Err(malformed(page_at, "page delta", 0, "pages not strictly ascending"));
+            }
+            let page = prev_page.saturating_add(delta);
+            prev_page = page;
+
+            let mask_at = rd.off;
+            let mask = if head & 1 == 1 {
+                let bit = rd.u8("bit index")?;
+                if bit > 63 {
+                    return Err(malformed(mask_at, "bit index", bit as u64, "must be < 64"));
+                }
+                1u64 << bit
+            } else {
+                let mask = rd.u64("mask")?;
+                if mask.count_ones() == 1 {
+                    return Err(malformed(mask_at, "mask", mask, "single-bit mask must use bit index form"));
+                }
+                mask
+            };
+            check.page(page_at, page, mask_at, mask)?;
+            visit(pidx, page, mask);
+        }
+    }
+
+    check.finish()
+}
@@ -0,0 +1,135 @@
+//! Streaming codec: payload from any `io::Read` to a container on any
+//! `io::Write`, and back.
+
+use std::io::{BufWriter, ErrorKind, Read, Write};
+
+use crate::alphabet::Packer;
+use crate::integrity::Hasher;
+use crate::{
+    cvp2, payload_chunks, peel_verified, Alphabet, CanvasConfig, CanvasView, CvpError, DecodeOptions, EncodeOptions,
+    EraseState, Result,
+};
+
+/// Incremental encoder. Memory is the canvas state (RG planes and A bitset);
+/// neither the payload nor the output is ever held in full.
+///
+/// The reference encoder erases steps N..1, but the resulting planes are sums
+/// of per-step `k`, so consuming the payload forward (1..N) yields a
+/// byte-identical container without needing a seekable source.
+pub struct Encoder {
+    opts: EncodeOptions,
+    state: EraseState,
+    hasher: Hasher,
+    /// Pending bits for `Alphabet::Nine`.
+    packer: Option<Packer>,
+    n: u64,
+}
+
+impl Encoder {
+    /// `BadDims` if the alphabet does not fit the canvas width.
+    pub fn new(opts: EncodeOptions) -> Result<Self> {
+        opts.alphabet.check(opts.profile.cfg)?;
+        let packer = (opts.alphabet == Alphabet::Nine).then(Packer::new);
+        Ok(Self { state: EraseState::new(&opts), opts, hasher: Hasher::new(), packer, n: 0 })
+    }
+
+    /// Steps erased so far (complete symbols for `Alphabet::Nine`).
+    pub fn len(&self) -> u64 {
+        self.n
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.n == 0
+    }
+
+    /// Erase the next payload bytes (steps n+1, n+2, ...).
+    pub fn update(&mut self, chunk: &[u8]) -> Result<()> {
+        for &b in chunk {
+            let symbol = match &mut self.packer {
+                None => b as u16,
+                Some(packer) => match packer.push(b) {
+                    Some(symbol) => symbol,
+                    None => continue,
+                },
+            };
+            self.n += 1;
+            self.state.erase(self.n, symbol)?;
+        }
+        self.hasher.update(chunk);
+        Ok(())
+    }
+
+    /// Write the container; returns the number of bytes written.
+    pub fn finish<W: Write>(mut self, out: W) -> Result<u64> {
+        let (last, pad_bits) = self.packer.take().map_or((None, 0), Packer::finish);
+        if let Some(symbol) = last {
+            self.n += 1;
+            self.state.erase(self.n, symbol)?;
+        }
+        if self.n == 0 { return Err(CvpError::EmptyPayload); }
+        let c = self.state.into_container(self.n, pad_bits, Some(self.hasher.finish()), &self.opts);
+        cvp2::pack_to(&c, BufWriter::new(out))
+    }
+
+    /// Read `input` to EOF and write the container to `output`.
+    pub fn encode<R: Read, W: Write>(opts: EncodeOptions, mut input: R, output: W) -> Result<u64> {
+        let mut enc = Encoder::new(opts)?;
+        let mut buf = vec![0u8; 64 * 1024];
+        loop {
+            let got = match input.read(&mut buf) {
+                Ok(0) => break,
+                Ok(got) => got,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e.into()),
+            };
+            enc.update(&buf[..got])?;
+        }
+        enc.finish(output)
+    }
+}
+
+/// Verified decoder. The container is parsed and the whole peel runs in
+/// `new` — A empty, RG FULL and the integrity trailer are all checked before
+/// a `Decoder` exists — so a verification failure never leaves partial
+/// payload in the output. Only an I/O error from the writer can do that.
+///
+/// Memory is the step index (4 bytes per step) plus the raw container when
+/// read through `decode`.
+pub struct Decoder {
+    header: cvp2::Header,
+    cfg: CanvasConfig,
+    step_to_pidx: Vec<u32>,
+}
+
+impl Decoder {
+    pub fn new(raw: &[u8], opts: &DecodeOptions) -> Result<Self> {
+        let view = CanvasView::with_options(raw, opts)?;
+        let step_to_pidx = peel_verified(&view, opts)?;
+        Ok(Self { header: view.header().clone(), cfg: view.config(), step_to_pidx })
+    }
+
+    /// Payload length in bytes.
+    pub fn len(&self) -> u64 {
+        self.header.payload_len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Write the payload in forward order; returns the number of bytes written.
+    pub fn write_to<W: Write>(&self, out: W) -> Result<u64> {
+        let mut out = BufWriter::new(out);
+        payload_chunks(&self.header, self.cfg, &self.step_to_pidx[1..], |chunk| Ok(out.write_all(chunk)?))?;
+        out.flush()?;
+        Ok(self.len())
+    }
+
+    /// Read a container from `input` to EOF, verify it, then write the
+    /// payload to `output`. Nothing is written unless verification passes.
+    pub fn decode<R: Read, W: Write>(opts: &DecodeOptions, mut input: R, output: W) -> Result<u64> {
+        let mut raw = Vec::new();
+        input.read_to_end(&mut raw)?;
+        Decoder::new(&raw, opts)?.write_to(output)
+    }
+}
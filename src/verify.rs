@@ -0,0 +1,121 @@
+//! Proving a file decodes without producing its payload.
+//!
+//! `verify` runs exactly the checks of `decode_with` (structure, step index,
+//! peel, A empty and RG back at baseline, integrity) but only keeps the step
+//! index; the integrity digests are fed from it chunk by chunk. Chains and
+//! archives are verified canvas by canvas, stopping at the first failure.
+
+use std::fmt;
+
+use crate::sections::malformed;
+use crate::{archive, chain, cvp2, peel_staged, CanvasView, CvpError, DecodeOptions, MAGIC};
+
+/// Where in the decode pipeline a check failed, in pipeline order.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stage {
+    /// Magic, header, sections, chain manifest or archive index.
+    Parse,
+    /// RG planes against A (`DecodeOptions::cross_check` only).
+    CrossCheck,
+    /// Building the step->pidx index from A.
+    StepIndex,
+    /// Steps N..1: A clear, then RG fill.
+    Peel,
+    /// A empty, RG back at baseline and 9-bit padding zero.
+    Convergence,
+    /// Integrity digests against the recovered bytes.
+    Integrity,
+}
+
+impl fmt::Display for Stage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Stage::Parse => "parse",
+            Stage::CrossCheck => "cross-check",
+            Stage::StepIndex => "step index",
+            Stage::Peel => "peel",
+            Stage::Convergence => "convergence",
+            Stage::Integrity => "integrity",
+        })
+    }
+}
+
+/// What a file that verified holds.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Verified {
+    /// "CVP1", "CVP2", "CVPC" or "CVPA".
+    pub format: &'static str,
+    pub canvases: u32,
+    /// Steps peeled across all canvases.
+    pub steps: u64,
+    pub payload_len: u64,
+    /// True if every canvas carried an integrity trailer.
+    pub integrity: bool,
+}
+
+/// The first failing check.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VerifyError {
+    pub stage: Stage,
+    /// Canvas index (manifest order) for chains and archives.
+    pub canvas: Option<u32>,
+    pub error: CvpError,
+}
+
+impl fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.canvas {
+            Some(index) => write!(f, "{} failed in canvas {}: {}", self.stage, index, self.error),
+            None => write!(f, "{} failed: {}", self.stage, self.error),
+        }
+    }
+}
+
+impl std::error::Error for VerifyError {}
+
+fn parse_error(error: CvpError) -> VerifyError {
+    VerifyError { stage: Stage::Parse, canvas: None, error }
+}
+
+/// Check that `raw` (CVP1, CVP2, CVPC or CVPA) decodes, discarding the bytes.
+pub fn verify(raw: &[u8], opts: &DecodeOptions) -> Result<Verified, VerifyError> {
+    if raw.len() < 4 { return Err(parse_error(CvpError::TooSmall { len: raw.len() })); }
+    let magic: &[u8; 4] = raw[0..4].try_into().unwrap();
+    if magic == MAGIC || magic == cvp2::MAGIC {
+        return verify_canvas(raw, opts).map_err(|(stage, error)| VerifyError { stage, canvas: None, error });
+    }
+    let (format, body) = match magic {
+        m if m == chain::MAGIC => ("CVPC", raw),
+        m if m == archive::MAGIC => ("CVPA", archive::body(raw).map_err(parse_error)?),
+        _ => return Err(parse_error(CvpError::BadMagic { found: *magic })),
+    };
+    let mut total = Verified { format, canvases: 0, steps: 0, payload_len: 0, integrity: true };
+    if body.is_empty() {
+        return Ok(total);
+    }
+    for (index, link) in chain::links(body).map_err(parse_error)?.iter().enumerate() {
+        let fail = |(stage, error)| VerifyError { stage, canvas: Some(index as u32), error };
+        let one = verify_canvas(link.raw, opts).map_err(fail)?;
+        if one.payload_len != link.payload_len {
+            let error = malformed(link.entry_offset, "payload_len", link.payload_len, "disagrees with canvas");
+            return Err(fail((Stage::Parse, error)));
+        }
+        total.canvases += 1;
+        total.steps += one.steps;
+        total.payload_len += one.payload_len;
+        total.integrity &= one.integrity;
+    }
+    Ok(total)
+}
+
+fn verify_canvas(raw: &[u8], opts: &DecodeOptions) -> Result<Verified, (Stage, CvpError)> {
+    let view = CanvasView::with_options(raw, opts).map_err(|e| (Stage::Parse, e))?;
+    peel_staged(&view, opts)?;
+    Ok(Verified {
+        format: if &raw[0..4] == MAGIC { "CVP1" } else { "CVP2" },
+        canvases: 1,
+        steps: view.header().n,
+        payload_len: view.header().payload_len(),
+        integrity: view.integrity().is_some(),
+    })
+}
@@ -0,0 +1,221 @@
+//! Borrowed, zero-copy view of a CVP1/CVP2 file.
+//!
+//! `CanvasView::new` runs the same structural validation as `unpack` but keeps
+//! only slices into the input, so a view over an mmap costs no copies. RG
+//! values are read straight from the plane bytes and A entries are decoded
+//! lazily by the iterators below, neither of which allocates.
+
+use crate::sections::{plane_value, Reader};
+use crate::{
+    cvp2, rg_from_a_with, ABitset, CanvasConfig, Container, CvpError, DecodeOptions, Integrity, Metadata, RGCanvas,
+    Result,
+};
+
+#[derive(Clone, Debug)]
+pub struct CanvasView<'a> {
+    pub(crate) header: cvp2::Header,
+    /// R plane then G plane as stored; `None` under FLAG_RG_ELIDED.
+    pub(crate) planes: Option<&'a [u8]>,
+    /// Validated A section body.
+    pub(crate) a: &'a [u8],
+    pub(crate) integrity: Option<Integrity>,
+    pub(crate) metadata: Option<Metadata>,
+}
+
+impl<'a> CanvasView<'a> {
+    /// Validate `raw` and borrow it; dispatches on magic like `unpack`.
+    /// The canvas must be within the default `DecodeOptions::max_pixels`.
+    pub fn new(raw: &'a [u8]) -> Result<Self> {
+        Self::with_options(raw, &DecodeOptions::default())
+    }
+
+    /// `new` with the canvas limit of `opts`.
+    pub fn with_options(raw: &'a [u8], opts: &DecodeOptions) -> Result<Self> {
+        if raw.len() < 4 { return Err(CvpError::TooSmall { len: raw.len() }); }
+        match &raw[0..4] {
+            m if m == crate::MAGIC => crate::cvp1_view(raw, opts),
+            m if m == cvp2::MAGIC => cvp2::view(raw, opts),
+            m => Err(CvpError::BadMagic { found: m.try_into().unwrap() }),
+        }
+    }
+
+    /// CVP1 files are reported with a synthesized CVP2 header, as by `unpack`.
+    pub fn header(&self) -> &cvp2::Header {
+        &self.header
+    }
+
+    pub fn config(&self) -> CanvasConfig {
+        self.header.profile.cfg
+    }
+
+    pub fn n(&self) -> u64 {
+        self.header.n
+    }
+
+    pub fn integrity(&self) -> Option<Integrity> {
+        self.integrity
+    }
+
+    /// The metadata section, if the writer stored one (never for CVP1).
+    pub fn metadata(&self) -> Option<&Metadata> {
+        self.metadata.as_ref()
+    }
+
+    /// True if the file stores no planes and R/G reads are derived from A.
+    pub fn rg_elided(&self) -> bool {
+        self.planes.is_none()
+    }
+
+    pub fn r(&self, pidx: u32) -> Result<u64> {
+        self.lane(0, pidx)
+    }
+
+    pub fn g(&self, pidx: u32) -> Result<u64> {
+        self.lane(1, pidx)
+    }
+
+    /// B plane; only present on four-lane canvases.
+    pub fn b(&self, pidx: u32) -> Result<u64> {
+        self.lane(2, pidx)
+    }
+
+    /// Alpha plane; only present on four-lane canvases.
+    pub fn alpha(&self, pidx: u32) -> Result<u64> {
+        self.lane(3, pidx)
+    }
+
+    /// Stored lane value of `pidx` (0=R, 1=G, 2=B, 3=alpha). For RG-elided
+    /// files the value is recomputed from the pixel's A entry, which scans
+    /// the A section.
+    pub fn lane(&self, lane: u8, pidx: u32) -> Result<u64> {
+        let profile = self.header.profile;
+        let pixels = profile.cfg.pixels();
+        let lanes = profile.lanes.count() as u64;
+        if lane as u64 >= lanes {
+            return Err(CvpError::FormatLimit { format: "canvas", field: "lane", value: lane as u64, max: lanes - 1 });
+        }
+        if pidx as usize >= pixels { return Err(CvpError::PidxOutOfRange { pidx }); }
+        if let Some(planes) = self.planes {
+            return Ok(plane_value(planes, lane as usize * pixels + pidx as usize));
+        }
+        let baseline = profile.baseline;
+        let mut v = baseline.value();
+        if let Some(entry) = self.a_entries().find(|e| e.pidx == pidx) {
+            for (page, mask) in entry.pages {
+                let mut m = mask;
+                while m != 0 {
+                    let step = (page << 6) + m.trailing_zeros() as u64;
+                    let (l, k) = profile.lane_k(step);
+                    if l == lane {
+                        v = baseline.erase(v, lane, k, step, pidx)?;
+                    }
+                    m &= m - 1;
+                }
+            }
+        }
+        Ok(v)
+    }
+
+    /// A entries in file order (ascending pidx for canonical files).
+    pub fn a_entries(&self) -> AEntries<'a> {
+        let varint = self.header.flags & cvp2::FLAG_A_VARINT != 0;
+        let mut rd = Reader::new(self.a, 0);
+        let left = if varint { rd.varint("entry_count") } else { rd.u32("entry_count").map(u64::from) };
+        AEntries { rd, left: left.unwrap_or(0), varint, prev_pidx: 0 }
+    }
+
+    /// Materialize the owned planes and bitset.
+    pub fn to_container(&self) -> Result<Container> {
+        let mut a = ABitset::new();
+        for entry in self.a_entries() {
+            a.db.insert(entry.pidx, entry.pages.collect());
+        }
+        let rg = match self.planes {
+            Some(planes) => {
+                let profile = self.header.profile;
+                let mut rg = RGCanvas::at_baseline(profile);
+                let pixels = profile.cfg.pixels();
+                for lane in 0..profile.lanes.count() {
+                    for (i, v) in rg.plane_mut(lane as u8).iter_mut().enumerate() {
+                        *v = plane_value(planes, lane * pixels + i);
+                    }
+                }
+                rg
+            }
+            None => rg_from_a_with(&a, self.header.profile)?,
+        };
+        let (integrity, metadata) = (self.integrity, self.metadata.clone());
+        Ok(Container { header: self.header.clone(), rg, a, integrity, metadata })
+    }
+}
+
+/// Iterator over the A entries of a `CanvasView`.
+pub struct AEntries<'a> {
+    rd: Reader<'a>,
+    left: u64,
+    varint: bool,
+    prev_pidx: u64,
+}
+
+/// One pixel's A entry: its pidx and an iterator over `(page, mask)`.
+pub struct AEntry<'a> {
+    pub pidx: u32,
+    pub pages: Pages<'a>,
+}
+
+impl<'a> Iterator for AEntries<'a> {
+    type Item = AEntry<'a>;
+
+    // The section was validated by the constructor, so reads cannot fail.
+    fn next(&mut self) -> Option<AEntry<'a>> {
+        if self.left == 0 { return None; }
+        self.left -= 1;
+        let (pidx, pcnt) = if self.varint {
+            let pidx = self.prev_pidx + self.rd.varint("pidx delta").ok()?;
+            self.prev_pidx = pidx;
+            (pidx as u32, self.rd.varint("page_count").ok()?)
+        } else {
+            (self.rd.u32("pidx").ok()?, self.rd.u16("page_count").ok()? as u64)
+        };
+        let pages = Pages { rd: self.rd.clone(), left: pcnt, varint: self.varint, prev_page: 0 };
+        if self.varint {
+            let mut skip = pages.clone();
+            skip.by_ref().for_each(drop);
+            self.rd = skip.rd;
+        } else {
+            self.rd.bytes(pcnt as usize * 12, "pages").ok()?;
+        }
+        Some(AEntry { pidx, pages })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.left as usize, Some(self.left as usize))
+    }
+}
+
+/// Iterator over one entry's `(page, mask)` pairs.
+#[derive(Clone)]
+pub struct Pages<'a> {
+    rd: Reader<'a>,
+    left: u64,
+    varint: bool,
+    prev_page: u64,
+}
+
+impl Iterator for Pages<'_> {
+    type Item = (u64, u64);
+
+    fn next(&mut self) -> Option<(u64, u64)> {
+        if self.left == 0 { return None; }
+        self.left -= 1;
+        if self.varint {
+            let head = self.rd.varint("page delta").ok()?;
+            let page = self.prev_page + (head >> 1);
+            self.prev_page = page;
+            let mask = if head & 1 == 1 { 1u64 << self.rd.u8("bit index").ok()? } else { self.rd.u64("mask").ok()? };
+            Some((page, mask))
+        } else {
+            Some((self.rd.u32("page").ok()? as u64, self.rd.u64("mask").ok()?))
+        }
+    }
+}
@@ -1,5 +1,5 @@
 use axum::{
-    body::{self, Bytes},
+    body::Bytes,
     http::StatusCode,
     response::IntoResponse,
     routing::{post, Router},
@@ -7,7 +7,7 @@ use axum::{
 use std::convert::Infallible;
 use tower_http::trace::TraceLayer;
 
-const MAX_FILE_SIZE: u64 = 1 * 1024 * 1024 * 1024; // 1 GB
+const MAX_FILE_SIZE: u64 = 1024 * 1024 * 1024; // 1 GB
 
 async fn encode(file: Bytes) -> Result<impl IntoResponse, Infallible> {
     if file.len() as u64 > MAX_FILE_SIZE {
@@ -37,8 +37,6 @@ async fn main() {
         .layer(TraceLayer::new_for_http());
     
     // Run the Axum server
-    axum::Server::bind(&"127.0.0.1:3000".parse().unwrap())
-        .serve(app.into_make_service())
-        .await
-        .unwrap();
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:3000").await.unwrap();
+    axum::serve(listener, app).await.unwrap();
 }
\ No newline at end of file
@@ -0,0 +1,67 @@
+use canvapress::archive::{self, Entry, NewEntry};
+use canvapress::chain::ChainOptions;
+use canvapress::{CvpError, DecodeOptions};
+
+mod common;
+
+fn file<'a>(path: &str, data: &'a [u8]) -> NewEntry<'a> {
+    NewEntry { path: path.to_string(), mode: 0o644, mtime: 1_700_000_000, data }
+}
+
+#[test]
+fn create_list_extract_add() {
+    let fw = common::pattern(9_000, 37, 256);
+    let opts = ChainOptions { max_steps: 4_000, ..Default::default() };
+    let files = [
+        file("etc/app.toml", b"threads = 4\n"),
+        file("empty", b""),
+        NewEntry { mode: 0o755, mtime: -5, ..file("fw/image.bin", &fw) },
+    ];
+    let raw = archive::create(&files, &opts).unwrap();
+
+    let entries = archive::list(&raw).unwrap();
+    let sizes: Vec<(&str, u64)> = entries.iter().map(|e| (e.path.as_str(), e.size)).collect();
+    assert_eq!(sizes, [("etc/app.toml", 12), ("empty", 0), ("fw/image.bin", 9_000)]);
+    assert_eq!(entries[2], Entry { path: "fw/image.bin".into(), size: 9_000, mode: 0o755, mtime: -5 });
+
+    let opts_d = DecodeOptions::default();
+    assert_eq!(archive::extract(&raw, "etc/app.toml", &opts_d).unwrap(), b"threads = 4\n");
+    assert_eq!(archive::extract(&raw, "empty", &opts_d).unwrap(), b"");
+    assert_eq!(archive::extract(&raw, "fw/image.bin", &opts_d).unwrap(), fw);
+    assert_eq!(archive::extract(&raw, "nope", &opts_d).unwrap_err(), CvpError::EntryNotFound { path: "nope".into() });
+
+    // add keeps the existing bytes in place and appends new canvases
+    let more = archive::add(&raw, &[file("notes.txt", b"v2")], &opts).unwrap();
+    let tail = &raw[raw.len() - 1_000..];
+    assert!(more.windows(tail.len()).any(|w| w == tail));
+    let all = archive::extract_all(&more, &opts_d).unwrap();
+    assert_eq!(all.len(), 4);
+    assert_eq!(all[2].1, fw);
+    assert_eq!((all[3].0.path.as_str(), all[3].1.as_slice()), ("notes.txt", &b"v2"[..]));
+
+    // an archive of empty entries has no body
+    let hollow = archive::create(&[file("a", b""), file("b/c", b"")], &opts).unwrap();
+    assert_eq!(archive::extract_all(&hollow, &opts_d).unwrap().len(), 2);
+}
+
+#[test]
+fn archive_rejections() {
+    let opts = ChainOptions::default();
+    for (path, reason) in [
+        ("/etc/passwd", "must be relative and /-separated"),
+        ("a/../b", "empty, . or .. component"),
+        ("a//b", "empty, . or .. component"),
+        ("", "empty path"),
+    ] {
+        let err = archive::create(&[file(path, b"x")], &opts).unwrap_err();
+        assert_eq!(err, CvpError::BadEntryPath { path: path.into(), reason });
+    }
+    let raw = archive::create(&[file("a", b"x")], &opts).unwrap();
+    let dup = archive::add(&raw, &[file("a", b"y")], &opts).unwrap_err();
+    assert_eq!(dup, CvpError::BadEntryPath { path: "a".into(), reason: "duplicate entry" });
+
+    // index claiming more bytes than the body holds: first entry's size 1 -> 2
+    let mut bad = raw.clone();
+    bad[10 + 2 + 1] = 2;
+    assert!(matches!(archive::list(&bad).unwrap_err(), CvpError::Malformed { field: "body", .. }));
+}
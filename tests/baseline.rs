@@ -0,0 +1,53 @@
+use canvapress::{
+    cvp2, cvp2_to_cvp1, decode_fill, decode_with, encode_with, raw_unpack, unpack, verify_rg_against_a, Baseline,
+    CvpError, DecodeOptions, EncodeOptions, Profile, RG_LIMIT_EXACT,
+};
+
+mod common;
+
+#[test]
+fn both_profiles_roundtrip() {
+    let payload = common::pattern(3_000, 7, 256);
+    for baseline in [Baseline::Full, Baseline::Zero] {
+        for rg_elided in [false, true] {
+            let profile = Profile { baseline, ..Default::default() };
+            let raw = encode_with(&payload, &EncodeOptions { profile, rg_elided, ..Default::default() }).unwrap();
+            let c = unpack(&raw).unwrap();
+            assert_eq!(c.header.profile.baseline, baseline);
+            assert_eq!(c.header.flags & cvp2::FLAG_BASELINE_ZERO != 0, baseline == Baseline::Zero);
+            assert_eq!(c.rg.profile.baseline, baseline);
+            assert!(verify_rg_against_a(&c.rg, &c.a).unwrap().is_empty());
+            assert!(decode_with(&raw, &DecodeOptions { cross_check: true, ..Default::default() }).unwrap() == payload);
+        }
+    }
+}
+
+#[test]
+fn lanes_move_away_from_the_baseline() {
+    // payload [0x05]: step 1 -> pidx 517, lane R, k=1
+    let profile = Profile { baseline: Baseline::Zero, ..Default::default() };
+    let full = raw_unpack(&encode_with(&[5], &EncodeOptions::default()).unwrap()).unwrap().0;
+    let zero = raw_unpack(&encode_with(&[5], &EncodeOptions { profile, ..Default::default() }).unwrap()).unwrap().0;
+    assert_eq!((full.r[517], full.r[0], full.g[517]), (RG_LIMIT_EXACT - 1, RG_LIMIT_EXACT, RG_LIMIT_EXACT));
+    assert_eq!((zero.r[517], zero.r[0], zero.g[517]), (1, 0, 0));
+}
+
+#[test]
+fn zero_profile_errors() {
+    let profile = Profile { baseline: Baseline::Zero, ..Default::default() };
+    let raw = encode_with(&[5], &EncodeOptions { profile, ..Default::default() }).unwrap();
+    let rg_at = |lane: usize, pidx: usize| 36 + 10 + (lane * canvapress::PIXELS + pidx) * 8;
+
+    let mut empty = raw.clone();
+    empty[rg_at(0, 517)..rg_at(0, 517) + 8].copy_from_slice(&0u64.to_le_bytes());
+    assert_eq!(decode_fill(&empty).unwrap_err(), CvpError::Underflow { lane: 0, step: 1, pidx: 517 });
+
+    let mut stray = raw.clone();
+    stray[rg_at(1, 9)..rg_at(1, 9) + 8].copy_from_slice(&3u64.to_le_bytes());
+    assert_eq!(decode_fill(&stray).unwrap_err(), CvpError::RgNotZero { lane: 1, pidx: 9, value: 3 });
+
+    assert_eq!(
+        cvp2_to_cvp1(&raw).unwrap_err(),
+        CvpError::FormatLimit { format: "CVP1", field: "baseline", value: 1, max: 0 }
+    );
+}
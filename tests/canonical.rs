@@ -0,0 +1,50 @@
+use canvapress::{canonicalize, encode_erase, raw_is_canonical, raw_unpack};
+use rand::{Rng, SeedableRng};
+
+/// Re-pack a RAW file with entries in descending pidx order and pages in
+/// descending page order, i.e. a valid but non-canonical serialization.
+fn pack_reversed(raw: &[u8]) -> Vec<u8> {
+    let (rg, a, _) = raw_unpack(raw).unwrap();
+    let mut out = raw[..24].to_vec();
+    for &v in rg.r.iter().chain(rg.g.iter()) {
+        out.extend_from_slice(&v.to_le_bytes());
+    }
+    out.extend_from_slice(&(a.db.len() as u32).to_le_bytes());
+    let mut pidxs: Vec<u32> = a.db.keys().copied().collect();
+    pidxs.sort_unstable_by_key(|&p| std::cmp::Reverse(p));
+    for pidx in pidxs {
+        let pages = &a.db[&pidx];
+        out.extend_from_slice(&pidx.to_le_bytes());
+        out.extend_from_slice(&(pages.len() as u16).to_le_bytes());
+        let mut list: Vec<(u32, u64)> = pages.iter().map(|(&p, &m)| (p as u32, m)).collect();
+        list.sort_unstable_by_key(|&(p, _)| std::cmp::Reverse(p));
+        for (page, mask) in list {
+            out.extend_from_slice(&page.to_le_bytes());
+            out.extend_from_slice(&mask.to_le_bytes());
+        }
+    }
+    out
+}
+
+#[test]
+fn encode_is_byte_identical() {
+    let mut rng = rand::rngs::StdRng::seed_from_u64(11);
+    let mut payload = vec![0u8; 8192];
+    for b in payload.iter_mut() { *b = rng.gen(); }
+
+    let a = encode_erase(&payload).unwrap();
+    let b = encode_erase(&payload).unwrap();
+    assert_eq!(a, b);
+    assert!(raw_is_canonical(&a).unwrap());
+}
+
+#[test]
+fn canonicalize_rewrites_reordered_file() {
+    let payload: Vec<u8> = (0..4096u32).map(|i| (i % 7) as u8).collect();
+    let raw = encode_erase(&payload).unwrap();
+    let reordered = pack_reversed(&raw);
+
+    assert_ne!(reordered, raw);
+    assert!(!raw_is_canonical(&reordered).unwrap());
+    assert_eq!(canonicalize(&reordered).unwrap(), raw);
+}
@@ -0,0 +1,58 @@
+use canvapress::{
+    capacity_report, capacity_report_with, encode_with, unpack, Alphabet, CanvasConfig, CvpError, EncodeOptions,
+    Lanes, Profile, Schedule, RG_LIMIT_EXACT,
+};
+
+mod common;
+
+#[test]
+fn report_matches_encoded_planes() {
+    let payload = common::pattern(30_000, 7, 256);
+    for opts in [
+        EncodeOptions::default(),
+        EncodeOptions {
+            profile: Profile { lanes: Lanes::Four, schedule: Schedule::ModularK(97), ..Default::default() },
+            ..Default::default()
+        },
+        EncodeOptions {
+            alphabet: Alphabet::Nine,
+            profile: Profile { cfg: CanvasConfig::new(512, 64).unwrap(), ..Default::default() },
+            ..Default::default()
+        },
+    ] {
+        let report = capacity_report_with(&payload, &opts).unwrap();
+        assert!(report.fits());
+        assert_eq!(report.max_safe_n, report.n);
+
+        // under FULL, the encoded lane value is RG_LIMIT minus the load
+        let rg = unpack(&encode_with(&payload, &opts).unwrap()).unwrap().rg;
+        let min = rg.planes().flatten().copied().min().unwrap();
+        assert_eq!(report.headroom(), Some(min));
+        let worst = report.worst(5);
+        assert_eq!(worst.len(), 5);
+        assert_eq!(worst[0].load, report.max_load);
+        assert!(worst.windows(2).all(|w| w[0].load >= w[1].load));
+        for p in worst {
+            assert_eq!(rg.plane(p.lane)[p.pidx as usize], RG_LIMIT_EXACT - p.load as u64);
+        }
+    }
+    assert_eq!(capacity_report(&payload).unwrap().n, 30_000);
+    assert_eq!(capacity_report(b"").unwrap_err(), CvpError::EmptyPayload);
+}
+
+#[test]
+fn predicts_underflow() {
+    // One-row canvas, one byte value: every step hits pidx 7 and each lane
+    // takes k = RG_LIMIT / 512 every other step, so lane R passes the limit
+    // on its 513th step (step 1025).
+    let opts = EncodeOptions { profile: common::one_row(RG_LIMIT_EXACT / 512), ..Default::default() };
+    let payload = [7u8; 2_000];
+    let report = capacity_report_with(&payload, &opts).unwrap();
+    assert!(!report.fits());
+    assert_eq!(report.headroom(), None);
+    assert_eq!((report.n, report.max_safe_n), (2_000, 1_024));
+    assert_eq!(report.max_load, 1_000 * (RG_LIMIT_EXACT / 512) as u128);
+
+    assert!(encode_with(&payload[..1_024], &opts).is_ok());
+    assert!(matches!(encode_with(&payload[..1_025], &opts), Err(CvpError::Underflow { pidx: 7, .. })));
+}
@@ -0,0 +1,88 @@
+use canvapress::chain::{self, ChainOptions};
+use canvapress::{
+    cvp2, cvp2_to_cvp1, decode_fill, encode_with, Alphabet, CvpError, DecodeOptions, EncodeOptions, RG_LIMIT_EXACT,
+};
+
+mod common;
+
+#[test]
+fn splits_where_one_canvas_would_underflow() {
+    // as in tests/capacity.rs: one canvas holds at most 1024 of these bytes
+    let encode = EncodeOptions { profile: common::one_row(RG_LIMIT_EXACT / 512), ..Default::default() };
+    let payload = [7u8; 2_500];
+    assert!(encode_with(&payload, &encode).is_err());
+
+    let raw = chain::encode_with(&payload, &ChainOptions { encode, ..Default::default() }).unwrap();
+    let links = chain::links(&raw).unwrap();
+    let lens: Vec<u64> = links.iter().map(|l| l.payload_len).collect();
+    assert_eq!(lens, [1_024, 1_024, 452]);
+    assert_eq!(decode_fill(links[2].raw).unwrap(), &payload[..452]);
+    assert_eq!(chain::decode(&raw).unwrap(), payload);
+}
+
+#[test]
+fn step_budget() {
+    let payload = common::pattern(10_000, 101, 256);
+    let opts = ChainOptions { max_steps: 3_000, ..Default::default() };
+    let raw = chain::encode_with(&payload, &opts).unwrap();
+    let lens: Vec<u64> = chain::links(&raw).unwrap().iter().map(|l| l.payload_len).collect();
+    assert_eq!(lens, [3_000, 3_000, 3_000, 1_000]);
+    assert_eq!(chain::decode(&raw).unwrap(), payload);
+
+    // 800 nine-bit steps hold 900 bytes
+    let encode = EncodeOptions { alphabet: Alphabet::Nine, ..Default::default() };
+    let nine = ChainOptions { encode, max_steps: 800 };
+    let raw = chain::encode_with(&payload, &nine).unwrap();
+    let links = chain::links(&raw).unwrap();
+    assert_eq!(links.len(), 12);
+    assert!(links[..11].iter().all(|l| l.payload_len == 900));
+    assert_eq!(chain::decode(&raw).unwrap(), payload);
+
+    // a payload that fits is still a chain of one
+    assert_eq!(chain::links(&chain::encode(b"one").unwrap()).unwrap().len(), 1);
+    assert_eq!(chain::encode(b"").unwrap_err(), CvpError::EmptyPayload);
+}
+
+#[test]
+fn chain_errors() {
+    let payload = common::pattern(3_000, 1, 256);
+    let raw = chain::encode_with(&payload, &ChainOptions { max_steps: 1_000, ..Default::default() }).unwrap();
+    let links = chain::links(&raw).unwrap();
+
+    // a canvas changed after encoding fails its own checks
+    let mut bad = raw.clone();
+    bad[links[1].offset + 100] ^= 1;
+    assert!(matches!(chain::decode(&bad).unwrap_err(), CvpError::Chain { index: 1, .. }));
+
+    // swapped canvases, an edited trailer or manifest length break the chain digest
+    let (first, second) = (links[0], links[1]);
+    assert_eq!(first.raw.len(), second.raw.len());
+    let mut swapped = raw.clone();
+    swapped[first.offset..second.offset].copy_from_slice(second.raw);
+    swapped[second.offset..second.offset + second.raw.len()].copy_from_slice(first.raw);
+    assert_eq!(chain::decode(&swapped).unwrap_err(), CvpError::ChainDigestMismatch);
+
+    let mut bad = raw.clone();
+    bad[links[1].offset + links[1].raw.len() - 1] ^= 1;
+    assert_eq!(chain::decode(&bad).unwrap_err(), CvpError::ChainDigestMismatch);
+    assert_eq!(chain::decode_range(&bad, 0..10, &DecodeOptions::default()).unwrap_err(), CvpError::ChainDigestMismatch);
+
+    let mut bad = raw.clone();
+    bad[chain::HEADER_LEN] = 0xe7; // first payload_len: 1000 -> 999
+    assert_eq!(chain::decode(&bad).unwrap_err(), CvpError::ChainDigestMismatch);
+
+    // every canvas needs a trailer for the digest
+    let cvp1 = cvp2_to_cvp1(&encode_with(b"no trailer", &EncodeOptions::default()).unwrap()).unwrap();
+    let mut bare = chain::encode(b"no trailer").unwrap();
+    bare.truncate(chain::HEADER_LEN + chain::MANIFEST_ENTRY_LEN);
+    bare[chain::HEADER_LEN + 8..].copy_from_slice(&(cvp1.len() as u64).to_le_bytes());
+    bare.extend_from_slice(&cvp1);
+    let missing = CvpError::MissingSection { tag: cvp2::SEC_INTEGRITY };
+    assert_eq!(chain::links(&bare).unwrap_err(), CvpError::Chain { index: 0, error: Box::new(missing) });
+
+    let mut bad = raw.clone();
+    bad.push(0);
+    assert!(matches!(chain::decode(&bad).unwrap_err(), CvpError::Malformed { field: "trailing bytes", .. }));
+    assert!(matches!(chain::decode(&raw[..10]).unwrap_err(), CvpError::Truncated { field: "digest", .. }));
+    assert!(matches!(chain::decode(&raw[..42]).unwrap_err(), CvpError::Malformed { field: "count", .. }));
+}
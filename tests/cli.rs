@@ -0,0 +1,44 @@
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+/// Empty scratch directory for one test.
+fn scratch(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("canvapress-cli-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn run(dir: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_canvapress")).current_dir(dir).args(args).output().unwrap()
+}
+
+#[test]
+fn extract_never_replaces_or_follows_links() {
+    let dir = scratch("extract");
+    std::fs::create_dir_all(dir.join("src/sub")).unwrap();
+    std::fs::write(dir.join("src/sub/a.txt"), b"archived").unwrap();
+    assert!(run(&dir, &["archive", "add", "x.cvpa", "src"]).status.success());
+
+    assert!(run(&dir, &["archive", "extract", "x.cvpa", "--to", "out"]).status.success());
+    assert_eq!(std::fs::read(dir.join("out/src/sub/a.txt")).unwrap(), b"archived");
+
+    // a second extraction finds the file in place and leaves it alone
+    std::fs::write(dir.join("out/src/sub/a.txt"), b"local edit").unwrap();
+    let out = run(&dir, &["archive", "extract", "x.cvpa", "--to", "out"]);
+    assert!(!out.status.success());
+    assert!(String::from_utf8_lossy(&out.stderr).contains("a.txt: extracting"));
+    assert_eq!(std::fs::read(dir.join("out/src/sub/a.txt")).unwrap(), b"local edit");
+
+    #[cfg(unix)]
+    {
+        std::fs::create_dir_all(dir.join("elsewhere")).unwrap();
+        std::fs::create_dir_all(dir.join("linked")).unwrap();
+        std::os::unix::fs::symlink(dir.join("elsewhere"), dir.join("linked/src")).unwrap();
+        let out = run(&dir, &["archive", "extract", "x.cvpa", "--to", "linked"]);
+        assert!(!out.status.success());
+        assert!(String::from_utf8_lossy(&out.stderr).contains("symbolic link"));
+        assert!(std::fs::read_dir(dir.join("elsewhere")).unwrap().next().is_none());
+    }
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,18 @@
+//! Fixtures shared by the integration tests; each test file pulls in what it
+//! needs with `mod common;`.
+#![allow(dead_code)]
+
+use canvapress::{CanvasConfig, Profile, Schedule};
+
+/// `len` bytes `i * k % m`: a deterministic payload that reaches every byte
+/// value below `m` when `k` is coprime to it.
+pub fn pattern(len: u32, k: u32, m: u32) -> Vec<u8> {
+    (0..len).map(|i| (i * k % m) as u8).collect()
+}
+
+/// 256x1 canvas with a constant `k`: every step of byte `b` hits pidx `b`,
+/// and each lane takes `k` on every other step, so a lane holds
+/// `RG_LIMIT_EXACT / k` steps of one byte value.
+pub fn one_row(k: u64) -> Profile {
+    Profile { cfg: CanvasConfig::new(256, 1).unwrap(), schedule: Schedule::ConstantK(k), ..Default::default() }
+}
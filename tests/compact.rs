@@ -0,0 +1,30 @@
+use canvapress::cvp2::{self, FLAG_RG_ELIDED, SECTION_HEADER_LEN};
+use canvapress::{cvp2_to_cvp1, decode_fill, encode_with, rg_from_a, unpack, CvpError, EncodeOptions, PIXELS};
+
+#[test]
+fn rg_elided_roundtrip_and_saving() {
+    let payload: Vec<u8> = (0..5000u32).map(|i| (i * 7 + i / 3) as u8).collect();
+    let full = encode_with(&payload, &EncodeOptions::default()).unwrap();
+    let compact = encode_with(&payload, &EncodeOptions { rg_elided: true, ..Default::default() }).unwrap();
+
+    assert_eq!(full.len() - compact.len(), SECTION_HEADER_LEN + 2 * PIXELS * 8);
+    assert_eq!(cvp2::read_header(&compact).unwrap().0.flags, FLAG_RG_ELIDED);
+    assert_eq!(decode_fill(&compact).unwrap(), payload);
+
+    // recomputed planes are exactly the stored ones
+    let c = unpack(&full).unwrap();
+    let rg = rg_from_a(&c.a).unwrap();
+    assert!(rg.r == c.rg.r && rg.g == c.rg.g);
+    assert!(cvp2_to_cvp1(&compact).unwrap() == cvp2_to_cvp1(&full).unwrap());
+}
+
+#[test]
+fn rg_section_rejected_in_elided_file() {
+    let full = encode_with(b"elided", &EncodeOptions::default()).unwrap();
+    let mut bad = full.clone();
+    bad[8] = FLAG_RG_ELIDED as u8;
+    assert_eq!(
+        decode_fill(&bad).unwrap_err(),
+        CvpError::Malformed { offset: 36, field: "section tag", value: 1, reason: "RG planes in RG-elided file" }
+    );
+}
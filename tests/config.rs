@@ -0,0 +1,77 @@
+use canvapress::{
+    cvp2, cvp2_to_cvp1, decode_fill, decode_range, decode_with, encode_erase, encode_with, unpack, verify,
+    CanvasConfig, CanvasView, CvpError, DecodeOptions, EncodeOptions, Profile,
+};
+use rand::{Rng, SeedableRng};
+
+mod common;
+
+fn payload(len: usize) -> Vec<u8> {
+    let mut rng = rand::rngs::StdRng::seed_from_u64(15);
+    (0..len).map(|_| rng.gen()).collect()
+}
+
+#[test]
+fn non_default_canvases_roundtrip() {
+    let payload = payload(9_000);
+    for (w, h) in [(256, 1), (256, 16), (1024, 1024)] {
+        let config = CanvasConfig::new(w, h).unwrap();
+        for rg_elided in [false, true] {
+            let profile = Profile { cfg: config, ..Default::default() };
+            let raw = encode_with(&payload, &EncodeOptions { profile, rg_elided, ..Default::default() }).unwrap();
+            let c = unpack(&raw).unwrap();
+            assert_eq!((c.header.profile.cfg.w(), c.header.profile.cfg.h()), (w, h));
+            assert_eq!(c.rg.r.len(), config.pixels());
+            assert_eq!(CanvasView::new(&raw).unwrap().config(), config);
+            assert!(decode_fill(&raw).unwrap() == payload);
+            assert_eq!(decode_range(&raw, 100..200).unwrap(), &payload[100..200]);
+        }
+    }
+}
+
+#[test]
+fn smaller_canvas_smaller_file() {
+    let payload = payload(2_000);
+    let small = CanvasConfig::new(256, 8).unwrap();
+    let profile = Profile { cfg: small, ..Default::default() };
+    let raw = encode_with(&payload, &EncodeOptions { profile, ..Default::default() }).unwrap();
+    assert!(raw.len() < encode_with(&payload, &EncodeOptions::default()).unwrap().len() / 20);
+}
+
+#[test]
+fn dims_are_validated() {
+    for (w, h) in [(128, 512), (300, 512), (512, 3), (1 << 16, 1 << 16), (0, 0)] {
+        assert_eq!(CanvasConfig::new(w, h).unwrap_err(), CvpError::BadDims { w, h });
+    }
+
+    // W at offset 12 in the CVP2 header
+    let mut raw = encode_with(b"dims", &EncodeOptions::default()).unwrap();
+    raw[12..16].copy_from_slice(&1000u32.to_le_bytes());
+    assert_eq!(cvp2::read_header(&raw).unwrap_err(), CvpError::BadDims { w: 1000, h: 512 });
+
+    let config = CanvasConfig::new(256, 64).unwrap();
+    let profile = Profile { cfg: config, ..Default::default() };
+    let raw = encode_with(b"not for CVP1", &EncodeOptions { profile, ..Default::default() }).unwrap();
+    assert_eq!(
+        cvp2_to_cvp1(&raw).unwrap_err(),
+        CvpError::FormatLimit { format: "CVP1", field: "W", value: 256, max: 512 }
+    );
+}
+
+#[test]
+fn decode_refuses_canvases_over_the_limit() {
+    // RG elided, so the file stays tiny whatever the canvas
+    let config = CanvasConfig::new(2048, 1024).unwrap();
+    let profile = Profile { cfg: config, ..Default::default() };
+    let raw = encode_with(b"x", &EncodeOptions { profile, rg_elided: true, ..Default::default() }).unwrap();
+    assert!(raw.len() < 200);
+
+    let too_large = CvpError::CanvasTooLarge { pixels: 1 << 21, max: 1 << 20 };
+    assert_eq!(decode_fill(&raw).unwrap_err(), too_large);
+    assert_eq!(verify(&raw, &DecodeOptions::default()).unwrap_err().error, too_large);
+
+    let opts = DecodeOptions { max_pixels: 1 << 21, ..Default::default() };
+    assert_eq!(decode_with(&raw, &opts).unwrap(), b"x");
+    let tight = DecodeOptions { max_pixels: 1 << 10, ..Default::default() };
+    assert!(matches!(decode_with(&encode_erase(b"x").unwrap(), &tight), Err(CvpError::CanvasTooLarge { .. })));
+}
@@ -0,0 +1,28 @@
+use canvapress::cvp2;
+use canvapress::{decode_with, encode_erase, unpack, verify_rg_against_a, CvpError, DecodeOptions, RgMismatch, RG_LIMIT_EXACT};
+
+#[test]
+fn reports_each_inconsistent_pixel() {
+    // payload [3, 3]: step 1 -> pidx 515 lane R k=1, step 2 -> pidx 1027 lane G k=1
+    let raw = encode_erase(&[3, 3]).unwrap();
+    let mut c = unpack(&raw).unwrap();
+    assert!(verify_rg_against_a(&c.rg, &c.a).unwrap().is_empty());
+
+    c.rg.r[515] = RG_LIMIT_EXACT;
+    c.rg.g[9] = 5;
+    assert_eq!(
+        verify_rg_against_a(&c.rg, &c.a).unwrap(),
+        vec![
+            RgMismatch { pidx: 515, lane: 0, expected: RG_LIMIT_EXACT - 1, stored: RG_LIMIT_EXACT },
+            RgMismatch { pidx: 9, lane: 1, expected: RG_LIMIT_EXACT, stored: 5 },
+        ]
+    );
+
+    let bad = cvp2::pack(&c).unwrap();
+    let opts = DecodeOptions { cross_check: true, ..Default::default() };
+    assert_eq!(
+        decode_with(&bad, &opts).unwrap_err(),
+        CvpError::RgInconsistent { lane: 0, pidx: 515, expected: RG_LIMIT_EXACT - 1, stored: RG_LIMIT_EXACT, count: 2 }
+    );
+    assert_eq!(decode_with(&raw, &opts).unwrap(), vec![3, 3]);
+}
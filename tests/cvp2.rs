@@ -0,0 +1,66 @@
+use canvapress::cvp2::{self, HEADER_LEN, SECTION_HEADER_LEN, SEC_A};
+use canvapress::{cvp1_to_cvp2, cvp2_to_cvp1, decode_fill, encode_with, raw_unpack, CvpError, EncodeOptions};
+
+mod common;
+
+fn sample() -> Vec<u8> {
+    let payload = common::pattern(2000, 13, 256);
+    encode_with(&payload, &EncodeOptions::default()).unwrap()
+}
+
+#[test]
+fn conversion_roundtrips_both_ways() {
+    let v2 = sample();
+    assert_eq!(&v2[0..4], cvp2::MAGIC);
+
+    let v1 = cvp2_to_cvp1(&v2).unwrap();
+    assert_eq!(&v1[0..4], b"CVP1");
+    assert_eq!(decode_fill(&v1).unwrap(), decode_fill(&v2).unwrap());
+    // CVP1 has no integrity trailer, so only the canvas survives the round trip
+    let back = cvp1_to_cvp2(&v1).unwrap();
+    assert_eq!(decode_fill(&back).unwrap(), decode_fill(&v2).unwrap());
+    assert!(cvp2_to_cvp1(&back).unwrap() == v1);
+
+    assert!(matches!(cvp1_to_cvp2(&v2), Err(CvpError::BadMagic { .. })));
+}
+
+#[test]
+fn unknown_sections_and_longer_headers_are_skipped() {
+    let v2 = sample();
+    let expect = decode_fill(&v2).unwrap();
+
+    let mut extra = v2.clone();
+    extra.extend_from_slice(&0x7f00u16.to_le_bytes());
+    extra.extend_from_slice(&3u64.to_le_bytes());
+    extra.extend_from_slice(b"new");
+    assert_eq!(decode_fill(&extra).unwrap(), expect);
+
+    // a future writer appends 4 header bytes
+    let mut long = v2[..HEADER_LEN as usize].to_vec();
+    long[6..8].copy_from_slice(&(HEADER_LEN + 4).to_le_bytes());
+    long.extend_from_slice(&[0xaa; 4]);
+    long.extend_from_slice(&v2[HEADER_LEN as usize..]);
+    assert_eq!(decode_fill(&long).unwrap(), expect);
+}
+
+#[test]
+fn header_rejections() {
+    let v2 = sample();
+
+    let mut ver = v2.clone();
+    ver[4] = 9;
+    assert_eq!(raw_unpack(&ver).unwrap_err(), CvpError::UnsupportedVersion { version: 9 });
+
+    let mut flags = v2.clone();
+    flags[8] = 0x80;
+    assert_eq!(raw_unpack(&flags).unwrap_err(), CvpError::UnsupportedFlags { flags: 0x80 });
+
+    let mut dup = v2.clone();
+    let (_, start) = cvp2::read_header(&v2).unwrap();
+    let a = cvp2::sections(&v2, start).unwrap().into_iter().find(|s| s.tag == SEC_A).unwrap();
+    dup.extend_from_slice(&v2[a.offset - SECTION_HEADER_LEN..]);
+    assert!(matches!(raw_unpack(&dup), Err(CvpError::Malformed { field: "section tag", .. })));
+
+    let no_a = v2[..a.offset - SECTION_HEADER_LEN].to_vec();
+    assert_eq!(raw_unpack(&no_a).unwrap_err(), CvpError::MissingSection { tag: SEC_A });
+}
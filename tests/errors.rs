@@ -0,0 +1,45 @@
+use canvapress::{decode_fill, encode_erase, raw_unpack, ABitset, AMismatch, CvpError, PIXELS, RG_LIMIT_EXACT};
+
+#[test]
+fn header_and_truncation_errors_are_typed() {
+    assert_eq!(raw_unpack(b"CV").unwrap_err(), CvpError::TooSmall { len: 2 });
+    assert_eq!(raw_unpack(b"XXXX").unwrap_err(), CvpError::BadMagic { found: *b"XXXX" });
+    assert_eq!(raw_unpack(b"CVP1\x00\x02").unwrap_err(), CvpError::Truncated { offset: 4, field: "W" });
+    assert_eq!(encode_erase(&[]).unwrap_err(), CvpError::EmptyPayload);
+}
+
+#[test]
+fn a_mismatch_carries_step_and_pidx() {
+    let mut a = ABitset::new();
+    a.set_step(7, 3);
+    assert_eq!(
+        a.clear_step(8, 3).unwrap_err(),
+        CvpError::AMismatch { step: 3, pidx: 8, kind: AMismatch::MissingPidx }
+    );
+    assert_eq!(
+        a.clear_step(7, 300).unwrap_err(),
+        CvpError::AMismatch { step: 300, pidx: 7, kind: AMismatch::MissingPage }
+    );
+    assert_eq!(
+        a.clear_step(7, 2).unwrap_err(),
+        CvpError::AMismatch { step: 2, pidx: 7, kind: AMismatch::BitAlreadyClear }
+    );
+}
+
+#[test]
+fn lane_overflow_and_convergence_errors() {
+    // payload [0x05]: step 1 -> y=1, x=5, pidx=517, lane R, k=1
+    let mut raw = encode_erase(&[5]).unwrap();
+    let r_at = |pidx: usize| 24 + pidx * 8;
+
+    let mut full = raw.clone();
+    full[r_at(517)..r_at(517) + 8].copy_from_slice(&RG_LIMIT_EXACT.to_le_bytes());
+    assert_eq!(decode_fill(&full).unwrap_err(), CvpError::Overflow { lane: 0, step: 1, pidx: 517 });
+
+    let g_at = 24 + (PIXELS + 9) * 8;
+    raw[g_at..g_at + 8].copy_from_slice(&(RG_LIMIT_EXACT - 1).to_le_bytes());
+    assert_eq!(
+        decode_fill(&raw).unwrap_err(),
+        CvpError::RgNotFull { lane: 1, pidx: 9, value: RG_LIMIT_EXACT - 1 }
+    );
+}
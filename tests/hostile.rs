@@ -0,0 +1,116 @@
+use canvapress::{decode_fill, encode_erase, raw_is_canonical, raw_unpack, CvpError, PIXELS, RG_LIMIT_EXACT, W};
+use rand::{Rng, SeedableRng};
+
+mod common;
+
+const A_OFF: usize = 24 + 2 * PIXELS * 8;
+
+fn sample() -> Vec<u8> {
+    let payload = common::pattern(300, 31, 256);
+    encode_erase(&payload).unwrap()
+}
+
+/// Replace the A section of `raw` with hand-written entries.
+fn with_entries(raw: &[u8], entries: &[(u32, &[(u32, u64)])]) -> Vec<u8> {
+    let mut out = raw[..A_OFF].to_vec();
+    out.extend_from_slice(&(entries.len() as u32).to_le_bytes());
+    for &(pidx, pages) in entries {
+        out.extend_from_slice(&pidx.to_le_bytes());
+        out.extend_from_slice(&(pages.len() as u16).to_le_bytes());
+        for &(page, mask) in pages {
+            out.extend_from_slice(&page.to_le_bytes());
+            out.extend_from_slice(&mask.to_le_bytes());
+        }
+    }
+    out
+}
+
+fn assert_rejected(raw: &[u8], expected: CvpError) {
+    assert_eq!(raw_unpack(raw).unwrap_err(), expected);
+    assert_eq!(decode_fill(raw).unwrap_err(), expected);
+}
+
+/// `Malformed` at `at` bytes into the A section.
+fn in_a(at: usize, field: &'static str, value: u64, reason: &'static str) -> CvpError {
+    CvpError::Malformed { offset: A_OFF + at, field, value, reason }
+}
+
+#[test]
+fn rejects_structural_inconsistencies() {
+    let raw = sample();
+    // N = 300 in the sample; step 1 lives in page 0 bit 1, on row 1. With one
+    // entry of one page, pidx is at 4, page_count at 8, page at 10, mask at 14.
+    let row1 = W + 5;
+    let pixels = PIXELS as u32;
+    assert_rejected(&with_entries(&raw, &[(pixels, &[(0, 2)])]), in_a(4, "pidx", PIXELS as u64, "out of range"));
+    assert_rejected(
+        &with_entries(&raw, &[(row1, &[(0, 2)]), (row1, &[(1, 1)])]),
+        in_a(22, "pidx", row1 as u64, "duplicated"),
+    );
+    assert_rejected(&with_entries(&raw, &[(row1, &[(0, 2), (0, 2)])]), in_a(22, "page", 0, "duplicated"));
+    assert_rejected(&with_entries(&raw, &[(row1, &[(0, 0)])]), in_a(14, "mask", 0, "empty page"));
+    assert_rejected(
+        &with_entries(&raw, &[(5, &[]), (6, &[(0, 2), (1, 1), (2, 1)]), (7, &[(1, 1)])]),
+        in_a(8, "page_count", 0, "zero or exceeds remaining bytes"),
+    );
+    assert_rejected(&with_entries(&raw, &[(5, &[(0, 1)])]), in_a(14, "mask", 1, "steps outside 1..=N"));
+    assert_rejected(&with_entries(&raw, &[(5, &[(9, 1)])]), in_a(10, "page", 9, "beyond N"));
+    assert_rejected(&with_entries(&raw, &[(row1, &[(0, 2)])]), CvpError::StepCountMismatch { bits: 1, n: 300 });
+
+    let mut huge = raw[..A_OFF].to_vec();
+    huge.extend_from_slice(&u32::MAX.to_le_bytes());
+    let reason = "exceeds pixels or remaining bytes";
+    assert_rejected(&huge, in_a(0, "entry_count", u32::MAX as u64, reason));
+
+    let mut trailing = raw.clone();
+    trailing.push(0);
+    let offset = raw.len();
+    assert_rejected(
+        &trailing,
+        CvpError::Malformed { offset, field: "trailing bytes", value: 1, reason: "after A section" },
+    );
+
+    let mut over = raw.clone();
+    over[24..32].copy_from_slice(&(RG_LIMIT_EXACT + 1).to_le_bytes());
+    assert_rejected(
+        &over,
+        CvpError::Malformed { offset: 24, field: "RG value", value: RG_LIMIT_EXACT + 1, reason: "exceeds RG_LIMIT" },
+    );
+}
+
+#[test]
+fn rejects_steps_off_their_pixel() {
+    // step 1 of [65] lands on column 65, row 1
+    let raw = encode_erase(&[65]).unwrap();
+    assert_eq!(with_entries(&raw, &[(W + 65, &[(0, 2)])]), raw);
+
+    let off_row = with_entries(&raw, &[(7 * W + 65, &[(0, 2)])]);
+    assert!(raw_is_canonical(&off_row).is_err());
+    assert_rejected(&off_row, in_a(14, "mask", 2, "steps off the pixel's row"));
+
+    let off_alphabet = with_entries(&raw, &[(W + 321, &[(0, 2)])]);
+    assert!(raw_is_canonical(&off_alphabet).is_err());
+    assert_rejected(&off_alphabet, in_a(4, "pidx", (W + 321) as u64, "column outside the alphabet"));
+}
+
+#[test]
+fn truncation_never_panics() {
+    let raw = sample();
+    for cut in (0..raw.len()).step_by(65_537).chain((A_OFF..raw.len()).step_by(97)) {
+        assert!(raw_unpack(&raw[..cut]).is_err(), "cut at {}", cut);
+    }
+}
+
+#[test]
+fn random_corruption_never_panics() {
+    let raw = sample();
+    let mut rng = rand::rngs::StdRng::seed_from_u64(3);
+    for _ in 0..40 {
+        let mut bad = raw.clone();
+        for _ in 0..rng.gen_range(1..4) {
+            let at = rng.gen_range(A_OFF..bad.len());
+            bad[at] = rng.gen();
+        }
+        let _ = decode_fill(&bad);
+    }
+}
@@ -0,0 +1,41 @@
+use canvapress::{
+    cvp2, cvp2_to_cvp1, encode_with, inspect, inspect_with, Alphabet, EncodeOptions, SectionSize, PIXELS,
+};
+
+#[test]
+fn inspect_reports_without_decoding() {
+    let payload: Vec<u8> = (0..5_000u32).map(|i| (i % 7) as u8).collect();
+    let raw = encode_with(&payload, &EncodeOptions::default()).unwrap();
+    let info = inspect_with(&raw, true).unwrap();
+    assert_eq!((info.format, info.payload_len, info.header.n), ("CVP2", 5_000, 5_000));
+    // 7 byte values over 512 rows, every (x, y) pair hit
+    assert_eq!((info.a_entries, info.pixels()), (7 * 512, PIXELS as u64));
+    assert_eq!(info.total_pages, 5_000);
+    assert_eq!(info.canonical, Some(true));
+    assert_eq!(inspect(&raw).unwrap().canonical, None);
+    let names: Vec<&str> = info.sections.iter().map(|s| s.name).collect();
+    assert_eq!(names, ["header", "RG", "A", "integrity"]);
+    assert_eq!(info.sections[1], SectionSize { name: "RG", tag: Some(cvp2::SEC_RG), bytes: 10 + PIXELS as u64 * 16 });
+    assert_eq!(info.sections.iter().map(|s| s.bytes).sum::<u64>(), raw.len() as u64);
+
+    let v1 = cvp2_to_cvp1(&raw).unwrap();
+    let info = inspect_with(&v1, true).unwrap();
+    assert_eq!(info.format, "CVP1");
+    let sizes: Vec<(&str, u64)> = info.sections.iter().map(|s| (s.name, s.bytes)).collect();
+    assert_eq!(sizes[..2], [("header", 24), ("RG", PIXELS as u64 * 16)]);
+    assert_eq!(info.sections.iter().map(|s| s.bytes).sum::<u64>(), v1.len() as u64);
+    assert!(info.canonical == Some(true) && info.integrity.is_none());
+
+    // an unknown section is reported; the file is no longer canonical
+    let mut extra = raw.clone();
+    extra.extend_from_slice(&99u16.to_le_bytes());
+    extra.extend_from_slice(&3u64.to_le_bytes());
+    extra.extend_from_slice(b"xyz");
+    let info = inspect_with(&extra, true).unwrap();
+    assert_eq!(info.canonical, Some(false));
+    assert_eq!(info.sections.last(), Some(&SectionSize { name: "unknown", tag: Some(99), bytes: 13 }));
+
+    let nine = encode_with(&payload, &EncodeOptions { alphabet: Alphabet::Nine, ..Default::default() }).unwrap();
+    let info = inspect(&nine).unwrap();
+    assert_eq!((info.header.n, info.payload_len, info.total_pages), (4_445, 5_000, 4_445));
+}
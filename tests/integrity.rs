@@ -0,0 +1,49 @@
+use canvapress::cvp2::{self, SEC_INTEGRITY};
+use canvapress::integrity::{crc32, sha256, Hasher};
+use canvapress::{decode_fill, encode_with, unpack, CvpError, EncodeOptions, Integrity};
+
+mod common;
+
+fn hex(b: &[u8]) -> String {
+    b.iter().map(|x| format!("{:02x}", x)).collect()
+}
+
+#[test]
+fn digest_known_vectors() {
+    assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    assert_eq!(hex(&sha256(b"")), "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855");
+    assert_eq!(hex(&sha256(b"abc")), "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+    assert_eq!(
+        hex(&sha256(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq")),
+        "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1"
+    );
+
+    // incremental updates across block boundaries match one-shot
+    let data = common::pattern(1000, 1, 256);
+    let mut h = Hasher::new();
+    for chunk in data.chunks(37) { h.update(chunk); }
+    assert_eq!(h.finish(), Integrity::of(&data));
+}
+
+#[test]
+fn encode_writes_trailer_and_decode_checks_it() {
+    let payload = b"integrity trailer payload".to_vec();
+    let raw = encode_with(&payload, &EncodeOptions::default()).unwrap();
+    assert_eq!(unpack(&raw).unwrap().integrity, Some(Integrity::of(&payload)));
+
+    // trailer is the last section
+    let (_, start) = cvp2::read_header(&raw).unwrap();
+    assert_eq!(cvp2::sections(&raw, start).unwrap().last().unwrap().tag, SEC_INTEGRITY);
+
+    // a converged canvas whose trailer describes different bytes
+    let mut c = unpack(&raw).unwrap();
+    c.integrity = Some(Integrity::of(b"something else entirely!!"));
+    assert_eq!(
+        decode_fill(&cvp2::pack(&c).unwrap()).unwrap_err(),
+        CvpError::IntegrityMismatch { crc32: true, sha256: true }
+    );
+
+    // the trailer is optional
+    c.integrity = None;
+    assert_eq!(decode_fill(&cvp2::pack(&c).unwrap()).unwrap(), payload);
+}
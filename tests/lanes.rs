@@ -0,0 +1,61 @@
+use canvapress::schedule::FourLane;
+use canvapress::{
+    cvp2, cvp2_to_cvp1, decode_with, encode_with, lane_k, unpack, CanvasView, CvpError, DecodeOptions,
+    EncodeOptions, LaneSchedule, Lanes, Profile, Schedule, RG_LIMIT_EXACT,
+};
+
+mod common;
+
+#[test]
+fn four_lanes_roundtrip() {
+    let payload = common::pattern(5_000, 29, 256);
+    for schedule in [Schedule::Linear, Schedule::ConstantK(2), Schedule::HashedLane(7)] {
+        for rg_elided in [false, true] {
+            let profile = Profile { lanes: Lanes::Four, schedule, ..Default::default() };
+            let raw = encode_with(&payload, &EncodeOptions { profile, rg_elided, ..Default::default() }).unwrap();
+            let c = unpack(&raw).unwrap();
+            assert_ne!(c.header.flags & cvp2::FLAG_FOUR_LANES, 0);
+            assert_eq!(c.rg.profile.lanes, Lanes::Four);
+            assert_eq!(c.rg.planes().count(), 4);
+            assert!(c.rg.b.iter().chain(&c.rg.alpha).any(|&v| v != RG_LIMIT_EXACT));
+            assert!(decode_with(&raw, &DecodeOptions { cross_check: true, ..Default::default() }).unwrap() == payload);
+
+            let view = CanvasView::new(&raw).unwrap();
+            let pidx = c.rg.alpha.iter().position(|&v| v != RG_LIMIT_EXACT).unwrap() as u32;
+            assert_eq!(view.alpha(pidx).unwrap(), c.rg.alpha[pidx as usize]);
+            assert_eq!(view.b(pidx).unwrap(), c.rg.b[pidx as usize]);
+        }
+    }
+
+    let two = unpack(&encode_with(&payload, &EncodeOptions::default()).unwrap()).unwrap();
+    assert!(two.rg.b.is_empty() && two.rg.alpha.is_empty());
+    assert!(CanvasView::new(&encode_with(&payload, &EncodeOptions::default()).unwrap()).unwrap().b(0).is_err());
+}
+
+#[test]
+fn four_lane_schedule_alternates_pairs() {
+    let lanes: Vec<u8> = (1..=8).map(|s| FourLane(canvapress::schedule::Linear).lane_k(s).0).collect();
+    assert_eq!(lanes, [0, 1, 2, 3, 0, 1, 2, 3]);
+    assert_eq!(FourLane(canvapress::schedule::Linear).lane_k(7).1, lane_k(7).1);
+}
+
+#[test]
+fn four_lanes_raise_capacity() {
+    // One-row canvas: every step of byte 7 hits pidx 7. With k = RG_LIMIT / 2
+    // a lane holds two steps, so eight steps overflow two lanes but not four.
+    let two = common::one_row(RG_LIMIT_EXACT / 2);
+    let payload = [7u8; 8];
+
+    let err = encode_with(&payload, &EncodeOptions { profile: two, ..Default::default() }).unwrap_err();
+    assert_eq!(err, CvpError::Underflow { lane: 1, step: 4, pidx: 7 });
+
+    let profile = Profile { lanes: Lanes::Four, ..two };
+    let raw = encode_with(&payload, &EncodeOptions { profile, ..Default::default() }).unwrap();
+    assert_eq!(decode_with(&raw, &DecodeOptions::default()).unwrap(), payload);
+    let profile = Profile { lanes: Lanes::Four, ..Default::default() };
+    let raw = encode_with(&payload[..1], &EncodeOptions { profile, ..Default::default() }).unwrap();
+    assert_eq!(
+        cvp2_to_cvp1(&raw).unwrap_err(),
+        CvpError::FormatLimit { format: "CVP1", field: "lanes", value: 4, max: 2 }
+    );
+}
@@ -0,0 +1,68 @@
+use canvapress::{
+    cvp2, cvp2_to_cvp1, decode_fill, encode_with, inspect, raw_is_canonical, CvpError, EncodeOptions,
+    Metadata,
+};
+use chrono::{TimeZone, Utc};
+
+fn sample() -> Metadata {
+    Metadata {
+        filename: Some("firmware-1.2.bin".into()),
+        mime_type: Some("application/octet-stream".into()),
+        created: Some(Utc.with_ymd_and_hms(2024, 5, 17, 8, 30, 0).unwrap() + chrono::Duration::nanoseconds(123)),
+        extra: [("board".to_string(), "rev-c".to_string()), ("note".to_string(), "=é=".to_string())].into(),
+    }
+}
+
+/// `raw` with a metadata section of `body` inserted before the integrity trailer.
+fn with_section(raw: &[u8], body: &[u8]) -> Vec<u8> {
+    let (_, start) = cvp2::read_header(raw).unwrap();
+    let trailer = cvp2::sections(raw, start).unwrap().into_iter().find(|s| s.tag == cvp2::SEC_INTEGRITY).unwrap();
+    let at = trailer.offset - cvp2::SECTION_HEADER_LEN;
+    let mut out = raw[..at].to_vec();
+    out.extend_from_slice(&cvp2::SEC_METADATA.to_le_bytes());
+    out.extend_from_slice(&(body.len() as u64).to_le_bytes());
+    out.extend_from_slice(body);
+    out.extend_from_slice(&raw[at..]);
+    out
+}
+
+#[test]
+fn metadata_roundtrip() {
+    let payload = b"payload bytes".repeat(100);
+    let raw = encode_with(&payload, &EncodeOptions { metadata: Some(sample()), ..Default::default() }).unwrap();
+    let info = inspect(&raw).unwrap();
+    assert_eq!(info.metadata, Some(sample()));
+    assert_eq!(info.header.n, payload.len() as u64);
+    assert!(info.integrity.is_some());
+
+    // informational only: decoding, canonical form and the trailer position are unaffected
+    assert_eq!(decode_fill(&raw).unwrap(), payload);
+    assert!(raw_is_canonical(&raw).unwrap());
+    let (_, start) = cvp2::read_header(&raw).unwrap();
+    let tags: Vec<u16> = cvp2::sections(&raw, start).unwrap().iter().map(|s| s.tag).collect();
+    assert_eq!(tags, [cvp2::SEC_RG, cvp2::SEC_A, cvp2::SEC_METADATA, cvp2::SEC_INTEGRITY]);
+
+    let plain = encode_with(&payload, &EncodeOptions::default()).unwrap();
+    assert_eq!(inspect(&plain).unwrap().metadata, None);
+    assert_eq!(inspect(&cvp2_to_cvp1(&raw).unwrap()).unwrap().metadata, None);
+    let empty = encode_with(&payload, &EncodeOptions { metadata: Some(Metadata::default()), ..Default::default() });
+    assert_eq!(inspect(&empty.unwrap()).unwrap().metadata, Some(Metadata::default()));
+}
+
+#[test]
+fn metadata_records() {
+    let plain = encode_with(b"abc", &EncodeOptions::default()).unwrap();
+
+    // unknown record kinds are skipped
+    let raw = with_section(&plain, &[9, 2, 0xff, 0xff, 1, 3, b'a', b'.', b'b']);
+    assert_eq!(inspect(&raw).unwrap().metadata.unwrap().filename.as_deref(), Some("a.b"));
+
+    let raw = with_section(&plain, &[1, 1, b'a', 1, 1, b'b']);
+    assert!(matches!(inspect(&raw).unwrap_err(), CvpError::Malformed { field: "metadata kind", value: 1, .. }));
+    let raw = with_section(&plain, &[2, 2, 0xc3, 0x28]);
+    assert!(matches!(inspect(&raw).unwrap_err(), CvpError::Malformed { field: "MIME type", .. }));
+    let raw = with_section(&plain, &[1, 5, b'a']);
+    assert!(matches!(inspect(&raw).unwrap_err(), CvpError::Malformed { field: "metadata len", value: 5, .. }));
+    let raw = with_section(&plain, &[4, 3, 5, b'k', b'v']);
+    assert!(matches!(inspect(&raw).unwrap_err(), CvpError::Malformed { field: "key len", value: 5, .. }));
+}
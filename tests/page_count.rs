@@ -0,0 +1,48 @@
+use canvapress::cvp2::{self, FLAG_A_VARINT};
+use canvapress::{cvp2_to_cvp1, decode_fill, encode_with, raw_pack, ABitset, CvpError, EncodeOptions, RGCanvas};
+
+mod common;
+
+/// CVP1 refuses a pixel with more pages than its u16 page_count holds.
+#[test]
+fn cvp1_refuses_page_count_beyond_u16() {
+    let mut a = ABitset::new();
+    for page in 0..=u16::MAX as u64 + 1 {
+        a.set_step(0, page << 6 | 1);
+    }
+    assert_eq!(
+        raw_pack(&RGCanvas::new(true), &a, 65_537).unwrap_err(),
+        CvpError::FormatLimit { format: "CVP1", field: "page_count", value: 65537, max: 65535 }
+    );
+}
+
+/// On a one-row canvas a constant payload puts every step on the same pixel,
+/// so 64 * 65536 + 1 steps give it 65537 pages; encode switches to varint A.
+#[test]
+fn constant_payload_falls_back_to_varint_a() {
+    let opts = EncodeOptions { profile: common::one_row(1), ..Default::default() };
+    let payload = vec![0u8; 64 * (u16::MAX as usize + 1) + 1];
+
+    let raw = encode_with(&payload, &opts).unwrap();
+    assert_ne!(cvp2::read_header(&raw).unwrap().0.flags & FLAG_A_VARINT, 0);
+    assert!(decode_fill(&raw).unwrap() == payload);
+}
+
+/// A constant payload revisits pixel (0, y) every 512 steps, each time in a new
+/// 64-step page, so N > 512 * 65535 overflows CVP1's u16 page_count. Writes
+/// 33.5 MB; run with `--ignored`.
+#[test]
+#[ignore]
+fn constant_payload_beyond_u16_page_count() {
+    let n = 512 * (u16::MAX as usize + 1) + 512;
+    let payload = vec![0u8; n];
+
+    let raw = encode_with(&payload, &EncodeOptions::default()).unwrap();
+    assert_ne!(cvp2::read_header(&raw).unwrap().0.flags & FLAG_A_VARINT, 0);
+    assert!(decode_fill(&raw).unwrap() == payload);
+
+    assert_eq!(
+        cvp2_to_cvp1(&raw).unwrap_err(),
+        CvpError::FormatLimit { format: "CVP1", field: "page_count", value: 65537, max: 65535 }
+    );
+}
@@ -0,0 +1,37 @@
+use canvapress::{decode_range, decode_range_with, encode_erase, encode_with, CvpError, DecodeOptions, EncodeOptions};
+use rand::{Rng, SeedableRng};
+
+#[test]
+fn range_matches_full_decode() {
+    let mut rng = rand::rngs::StdRng::seed_from_u64(14);
+    let payload: Vec<u8> = (0..20_000).map(|_| rng.gen()).collect();
+    let opts = EncodeOptions { rg_elided: true, a_varint: true, ..Default::default() };
+
+    for raw in [encode_erase(&payload).unwrap(), encode_with(&payload, &opts).unwrap()] {
+        for (start, end) in [(0, 1), (0, 20_000), (63, 129), (12_345, 12_400), (19_999, 20_000), (500, 500)] {
+            let expect = &payload[start..end];
+            let range = start as u64..end as u64;
+            assert_eq!(decode_range(&raw, range.clone()).unwrap(), expect);
+            let verify = DecodeOptions { cross_check: true, ..Default::default() };
+            assert_eq!(decode_range_with(&raw, range, Some(&verify)).unwrap(), expect);
+        }
+        assert_eq!(
+            decode_range(&raw, 10..20_001).unwrap_err(),
+            CvpError::RangeOutOfBounds { start: 10, end: 20_001, len: 20_000 }
+        );
+    }
+}
+
+#[test]
+fn verify_catches_what_the_range_skips() {
+    let payload = b"partial decode".repeat(20);
+    let mut raw = encode_with(&payload, &EncodeOptions::default()).unwrap();
+    let last = raw.len() - 1;
+    raw[last] ^= 1;
+
+    assert_eq!(decode_range(&raw, 2..9).unwrap(), &payload[2..9]);
+    assert_eq!(
+        decode_range_with(&raw, 2..9, Some(&DecodeOptions::default())).unwrap_err(),
+        CvpError::IntegrityMismatch { crc32: false, sha256: true }
+    );
+}
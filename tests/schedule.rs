@@ -0,0 +1,71 @@
+use canvapress::schedule::{ConstantK, HashedLane, Linear, ModularK};
+use canvapress::{
+    cvp2, cvp2_to_cvp1, decode_fill, decode_with, encode_erase, encode_with, lane_k, unpack, CvpError,
+    DecodeOptions, EncodeOptions, LaneSchedule, Profile, Schedule,
+};
+
+mod common;
+
+const SCHEDULES: [Schedule; 4] =
+    [Schedule::Linear, Schedule::ConstantK(3), Schedule::ModularK(1000), Schedule::HashedLane(0xfeed)];
+
+#[test]
+fn schedules_roundtrip_and_are_recorded() {
+    let payload = common::pattern(4_000, 13, 256);
+    for schedule in SCHEDULES {
+        for rg_elided in [false, true] {
+            let profile = Profile { schedule, ..Default::default() };
+            let opts = EncodeOptions { profile, rg_elided, ..Default::default() };
+            let raw = encode_with(&payload, &opts).unwrap();
+            let (header, start) = cvp2::read_header(&raw).unwrap();
+            assert_eq!(header.profile.schedule, schedule);
+            assert_eq!(header.flags & cvp2::FLAG_LANE_SCHEDULE != 0, schedule != Schedule::Linear);
+            assert_eq!(start, header.encoded_len() as usize);
+            assert_eq!(unpack(&raw).unwrap().rg.profile.schedule, schedule);
+            assert!(decode_with(&raw, &DecodeOptions { cross_check: true, ..Default::default() }).unwrap() == payload);
+        }
+    }
+    // linear files are unchanged
+    let linear = encode_with(&payload, &EncodeOptions::default()).unwrap();
+    assert_eq!(cvp2_to_cvp1(&linear).unwrap(), encode_erase(&payload).unwrap());
+}
+
+#[test]
+fn schedule_functions() {
+    for step in [1, 2, 3, 64, 1001, u64::MAX] {
+        assert_eq!(Linear.lane_k(step), lane_k(step));
+        assert_eq!(ConstantK(7).lane_k(step), ((step % 2 == 0) as u8, 7));
+        assert_eq!(ModularK(10).lane_k(step).1, step % 10 + 1);
+        assert_eq!(HashedLane(1).lane_k(step).1, (step >> 1) + (step & 1));
+    }
+    let lanes: u64 = (1..=1000).map(|s| HashedLane(9).lane_k(s).0 as u64).sum();
+    assert!((400..600).contains(&lanes));
+
+    for s in SCHEDULES {
+        assert_eq!(s.to_string().parse::<Schedule>().unwrap(), s);
+        assert_eq!(Schedule::from_id(s.id(), s.param()).unwrap(), s);
+    }
+    assert!("constant:0".parse::<Schedule>().is_err());
+    assert!("spiral".parse::<Schedule>().is_err());
+}
+
+#[test]
+fn wrong_or_unknown_schedule_is_rejected() {
+    let payload = b"scheduled".repeat(40);
+    let profile = Profile { schedule: Schedule::ConstantK(5), ..Default::default() };
+    let raw = encode_with(&payload, &EncodeOptions { profile, ..Default::default() }).unwrap();
+
+    // schedule id at offset 36, param at 37
+    let mut other = raw.clone();
+    other[37..45].copy_from_slice(&6u64.to_le_bytes());
+    assert_eq!(decode_fill(&other).unwrap_err(), CvpError::Overflow { lane: 1, step: 360, pidx: 184_420 });
+
+    let mut unknown = raw.clone();
+    unknown[36] = 9;
+    assert_eq!(decode_fill(&unknown).unwrap_err(), CvpError::UnsupportedSchedule { id: 9, param: 5 });
+
+    assert_eq!(
+        cvp2_to_cvp1(&raw).unwrap_err(),
+        CvpError::FormatLimit { format: "CVP1", field: "schedule", value: 1, max: 0 }
+    );
+}
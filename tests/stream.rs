@@ -0,0 +1,102 @@
+use std::io::{self, Read, Write};
+
+mod common;
+
+use canvapress::stream::{Decoder, Encoder};
+use canvapress::{decode_fill, encode_with, CvpError, DecodeOptions, EncodeOptions};
+use rand::{Rng, SeedableRng};
+
+/// Reader that hands out at most 7 bytes per call.
+struct Trickle<'a>(&'a [u8]);
+
+impl Read for Trickle<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.0.len().min(buf.len()).min(7);
+        buf[..n].copy_from_slice(&self.0[..n]);
+        self.0 = &self.0[n..];
+        Ok(n)
+    }
+}
+
+struct Broken;
+
+impl Write for Broken {
+    fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+        Err(io::Error::new(io::ErrorKind::BrokenPipe, "closed"))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn streaming_matches_in_memory_encode() {
+    let mut rng = rand::rngs::StdRng::seed_from_u64(21);
+    let payload: Vec<u8> = (0..30_000).map(|_| rng.gen()).collect();
+
+    for opts in [
+        EncodeOptions::default(),
+        EncodeOptions { rg_elided: true, a_varint: true, ..Default::default() },
+    ] {
+        let expect = encode_with(&payload, &opts).unwrap();
+
+        let mut out = Vec::new();
+        let written = Encoder::encode(opts.clone(), Trickle(&payload), &mut out).unwrap();
+        assert_eq!(written, out.len() as u64);
+        assert!(out == expect);
+
+        let mut enc = Encoder::new(opts).unwrap();
+        for chunk in payload.chunks(4093) {
+            enc.update(chunk).unwrap();
+        }
+        assert_eq!(enc.len(), payload.len() as u64);
+        let mut out = Vec::new();
+        enc.finish(&mut out).unwrap();
+        assert!(out == expect);
+    }
+
+    let mut out = Vec::new();
+    Encoder::encode(EncodeOptions::default(), Trickle(&payload), &mut out).unwrap();
+    assert!(decode_fill(&out).unwrap() == payload);
+}
+
+#[test]
+fn streaming_errors() {
+    assert_eq!(Encoder::new(EncodeOptions::default()).unwrap().finish(Vec::new()).unwrap_err(), CvpError::EmptyPayload);
+    assert!(matches!(
+        Encoder::encode(EncodeOptions::default(), &b"abc"[..], Broken),
+        Err(CvpError::Io { kind: io::ErrorKind::BrokenPipe, .. })
+    ));
+}
+
+#[test]
+fn streaming_decode_matches_decode_fill() {
+    let payload = common::pattern(70_000, 131, 251);
+    let raw = encode_with(&payload, &EncodeOptions::default()).unwrap();
+
+    let mut out = Vec::new();
+    let written = Decoder::decode(&DecodeOptions::default(), Trickle(&raw), &mut out).unwrap();
+    assert_eq!(written, payload.len() as u64);
+    assert!(out == payload);
+
+    let dec = Decoder::new(&raw, &DecodeOptions { cross_check: true, ..Default::default() }).unwrap();
+    assert_eq!(dec.len(), payload.len() as u64);
+    let mut out = Vec::new();
+    dec.write_to(&mut out).unwrap();
+    assert!(out == payload);
+}
+
+#[test]
+fn failed_verification_writes_nothing() {
+    let payload = b"streamed payload".repeat(50);
+    let mut raw = encode_with(&payload, &EncodeOptions::default()).unwrap();
+    let last = raw.len() - 1;
+    raw[last] ^= 1; // SHA-256 in the integrity trailer
+
+    let mut out = Vec::new();
+    let err = Decoder::decode(&DecodeOptions::default(), &raw[..], &mut out).unwrap_err();
+    assert_eq!(err, CvpError::IntegrityMismatch { crc32: false, sha256: true });
+    assert!(out.is_empty());
+    assert_eq!(decode_fill(&raw).unwrap_err(), err);
+}
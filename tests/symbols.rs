@@ -0,0 +1,100 @@
+use canvapress::alphabet::{Packer, Unpacker};
+use canvapress::stream::{Decoder, Encoder};
+use canvapress::{
+    cvp2, cvp2_to_cvp1, decode_fill, decode_range, decode_range_with, encode_with, unpack, Alphabet, CanvasConfig,
+    CvpError, DecodeOptions, EncodeOptions, Profile,
+};
+
+mod common;
+
+#[test]
+fn nine_bit_roundtrip() {
+    let data: Vec<u8> = (0..20_000u32).map(|i| (i.wrapping_mul(2_654_435_761) >> 13) as u8).collect();
+    for len in (1..=40).chain([4_097, 20_000]) {
+        let payload = &data[..len];
+        for rg_elided in [false, true] {
+            let opts = EncodeOptions { alphabet: Alphabet::Nine, rg_elided, ..Default::default() };
+            let raw = encode_with(payload, &opts).unwrap();
+            let (header, start) = cvp2::read_header(&raw).unwrap();
+            assert_eq!(header.alphabet(), Alphabet::Nine);
+            assert_eq!((header.n, header.pad_bits), Alphabet::Nine.steps_for_len(len as u64));
+            assert_eq!(header.payload_len(), len as u64);
+            assert_eq!(start, cvp2::HEADER_LEN_SYMBOLS as usize);
+            assert_eq!(raw[45], header.pad_bits);
+            assert!(decode_fill(&raw).unwrap() == payload, "len {}", len);
+        }
+    }
+
+    // 8 bytes fill 8 symbols with 8 bits to spare; 9 bytes fill them exactly
+    assert_eq!(Alphabet::Nine.steps_for_len(8), (8, 8));
+    assert_eq!(Alphabet::Nine.steps_for_len(9), (8, 0));
+    let byte = encode_with(&data, &EncodeOptions::default()).unwrap();
+    let packed = encode_with(&data, &EncodeOptions { alphabet: Alphabet::Nine, ..Default::default() }).unwrap();
+    assert_eq!(unpack(&packed).unwrap().header.n, 17_778);
+    assert!(unpack(&packed).unwrap().a.db.keys().any(|&pidx| pidx % 512 >= 256));
+    assert!(unpack(&byte).unwrap().a.db.keys().all(|&pidx| pidx % 512 < 256));
+}
+
+#[test]
+fn nine_bit_ranges_and_stream() {
+    let payload = common::pattern(3_000, 31, 251);
+    let opts = EncodeOptions { alphabet: Alphabet::Nine, rg_elided: true, ..Default::default() };
+    let raw = encode_with(&payload, &opts).unwrap();
+    for (start, end) in [(0, 0), (0, 1), (1, 9), (8, 9), (17, 400), (2_991, 3_000), (0, 3_000)] {
+        let want = &payload[start as usize..end as usize];
+        assert_eq!(decode_range(&raw, start..end).unwrap(), want);
+        assert_eq!(decode_range_with(&raw, start..end, Some(&DecodeOptions::default())).unwrap(), want);
+    }
+    let err = CvpError::RangeOutOfBounds { start: 0, end: 3_001, len: 3_000 };
+    assert_eq!(decode_range(&raw, 0..3_001).unwrap_err(), err);
+
+    let mut streamed = Vec::new();
+    let mut enc = Encoder::new(opts).unwrap();
+    payload.chunks(7).for_each(|c| enc.update(c).unwrap());
+    enc.finish(&mut streamed).unwrap();
+    assert_eq!(streamed, raw);
+
+    let dec = Decoder::new(&raw, &DecodeOptions { cross_check: true, ..Default::default() }).unwrap();
+    assert_eq!(dec.len(), 3_000);
+    let mut out = Vec::new();
+    dec.write_to(&mut out).unwrap();
+    assert_eq!(out, payload);
+
+    let (symbols, pad) = Packer::pack(&payload);
+    let mut bytes = Vec::new();
+    let mut unpacker = Unpacker::new();
+    symbols.iter().for_each(|&s| unpacker.push(s, &mut bytes));
+    assert_eq!((bytes, pad), (payload, 3));
+}
+
+#[test]
+fn nine_bit_rejections() {
+    let profile = Profile { cfg: CanvasConfig::new(256, 1024).unwrap(), ..Default::default() };
+    let narrow = EncodeOptions { alphabet: Alphabet::Nine, profile, ..Default::default() };
+    assert_eq!(encode_with(b"x", &narrow).unwrap_err(), CvpError::BadDims { w: 256, h: 1024 });
+    assert_eq!(Encoder::new(narrow).err(), Some(CvpError::BadDims { w: 256, h: 1024 }));
+
+    let raw = encode_with(b"nine bit!", &EncodeOptions { alphabet: Alphabet::Nine, ..Default::default() }).unwrap();
+    assert_eq!(
+        cvp2_to_cvp1(&raw).unwrap_err(),
+        CvpError::FormatLimit { format: "CVP1", field: "symbol bits", value: 9, max: 8 }
+    );
+
+    // N = 8: padding of 3 cannot make whole bytes
+    let mut bad = raw.clone();
+    bad[45] = 3;
+    assert!(matches!(decode_fill(&bad).unwrap_err(), CvpError::Malformed { offset: 45, field: "pad_bits", .. }));
+
+    // 8 bits of padding hides the last byte, which is not zero
+    bad[45] = 8;
+    assert_eq!(decode_fill(&bad).unwrap_err(), CvpError::PaddingNotZero { symbol: b'!' as u16, pad_bits: 8 });
+
+    // unused schedule fields (offsets 36..45) must stay zero
+    for (at, value) in [(36, 2), (37, 5)] {
+        let mut bad = raw.clone();
+        bad[at] = value;
+        let reason = "set without FLAG_LANE_SCHEDULE";
+        let err = CvpError::Malformed { offset: 36, field: "schedule", value: value as u64, reason };
+        assert_eq!(cvp2::read_header(&bad).unwrap_err(), err);
+    }
+}
@@ -0,0 +1,77 @@
+use canvapress::cvp2::{self, FLAG_A_VARINT, SEC_A};
+use canvapress::{
+    canonicalize, decode_fill, encode_with, raw_is_canonical, raw_pack, raw_pack_with, raw_unpack, AEncoding, CvpError,
+    EncodeOptions,
+};
+use rand::{Rng, SeedableRng};
+use std::collections::HashSet;
+
+fn a_section(raw: &[u8]) -> cvp2::Section<'_> {
+    let (_, start) = cvp2::read_header(raw).unwrap();
+    cvp2::sections(raw, start).unwrap().into_iter().find(|s| s.tag == SEC_A).unwrap()
+}
+
+#[test]
+fn varint_a_is_smaller_and_equivalent() {
+    let mut rng = rand::rngs::StdRng::seed_from_u64(5);
+    let payload: Vec<u8> = (0..20_000).map(|_| rng.gen()).collect();
+    let opts = EncodeOptions { a_varint: true, ..Default::default() };
+
+    let plain = encode_with(&payload, &EncodeOptions::default()).unwrap();
+    let compact = encode_with(&payload, &opts).unwrap();
+    assert_eq!(cvp2::read_header(&compact).unwrap().0.flags, FLAG_A_VARINT);
+    assert!(a_section(&compact).body.len() * 3 < a_section(&plain).body.len());
+
+    assert_eq!(decode_fill(&compact).unwrap(), payload);
+    let (rg, a1, n) = raw_unpack(&plain).unwrap();
+    let (_, a2, _) = raw_unpack(&compact).unwrap();
+    assert_eq!(a1.db, a2.db);
+
+    // raw_pack_with writes either encoding and raw_unpack reads both back
+    let v1 = raw_pack_with(&rg, &a1, n, AEncoding::Plain).unwrap();
+    assert!(v1 == raw_pack(&rg, &a1, n as u32).unwrap());
+    let v2 = raw_pack_with(&rg, &a1, n, AEncoding::Varint).unwrap();
+    assert_eq!(a_section(&v2).body, a_section(&compact).body);
+    for raw in [&v1, &v2] {
+        let (rg2, a3, n2) = raw_unpack(raw).unwrap();
+        assert!(rg2.r == rg.r && rg2.g == rg.g && a3.db == a1.db && n2 == n);
+    }
+    assert!(raw_is_canonical(&compact).unwrap());
+    assert!(canonicalize(&compact).unwrap() == compact);
+}
+
+#[test]
+fn varint_a_rejects_malformed_entries() {
+    // 513 equal bytes: 512 pixels, steps 1 and 513 share pixel (9, 1)
+    let payload = vec![9u8; 513];
+    let raw = encode_with(&payload, &EncodeOptions { a_varint: true, ..Default::default() }).unwrap();
+    let sec = a_section(&raw);
+
+    // flip the first entry's pidx delta to an out-of-range value
+    let mut bad = raw.clone();
+    let at = sec.offset + 2; // entry_count varint (2 bytes for 512 entries)
+    bad[at] = 0xff;
+    bad[at + 1] = 0xff;
+    assert_eq!(
+        decode_fill(&bad).unwrap_err(),
+        CvpError::Malformed { offset: at, field: "pidx", value: 294_911, reason: "out of range" }
+    );
+
+    // truncate the body and shrink the section len to match, so every cut
+    // reaches the varint parser instead of the TLV length check
+    let raw = encode_with(&payload, &EncodeOptions { a_varint: true, rg_elided: true, ..Default::default() }).unwrap();
+    let sec = a_section(&raw);
+    let mut fields = HashSet::new();
+    for cut in sec.offset..sec.offset + sec.body.len() {
+        let mut t = raw[..cut].to_vec();
+        t[sec.offset - 8..sec.offset].copy_from_slice(&((cut - sec.offset) as u64).to_le_bytes());
+        t.extend_from_slice(&raw[sec.offset + sec.body.len()..]);
+        let field = match raw_unpack(&t).unwrap_err() {
+            CvpError::Malformed { field, .. } | CvpError::Truncated { field, .. } => field,
+            e => panic!("cut at {}: {:?}", cut, e),
+        };
+        assert!(["entry_count", "pidx delta", "page_count", "page delta", "bit index", "mask"].contains(&field));
+        fields.insert(field);
+    }
+    assert!(fields.contains("pidx delta") && fields.contains("page_count"));
+}
@@ -0,0 +1,68 @@
+use canvapress::chain::{self, ChainOptions};
+use canvapress::{
+    archive, cvp2_to_cvp1, encode_with, inspect, verify, CvpError, DecodeOptions, EncodeOptions, Stage, Verified,
+    VerifyError,
+};
+
+mod common;
+
+/// Lower the R value of pidx 0 by one, so the peel ends one short of FULL.
+fn nudge_rg(raw: &mut [u8]) {
+    let at = inspect(raw).unwrap().sections[0].bytes as usize + 10;
+    let v = u64::from_le_bytes(raw[at..at + 8].try_into().unwrap());
+    raw[at..at + 8].copy_from_slice(&(v - 1).to_le_bytes());
+}
+
+#[test]
+fn verifies_every_container_kind() {
+    let payload = common::pattern(9_000, 37, 251);
+    let opts = DecodeOptions::default();
+    let raw = encode_with(&payload, &EncodeOptions::default()).unwrap();
+    let one = Verified { format: "CVP2", canvases: 1, steps: 9_000, payload_len: 9_000, integrity: true };
+    assert_eq!(verify(&raw, &opts), Ok(one));
+    let v1 = verify(&cvp2_to_cvp1(&raw).unwrap(), &opts).unwrap();
+    assert_eq!((v1.format, v1.integrity), ("CVP1", false));
+
+    let chained = chain::encode_with(&payload, &ChainOptions { max_steps: 4_000, ..Default::default() }).unwrap();
+    assert_eq!(verify(&chained, &opts), Ok(Verified { format: "CVPC", canvases: 3, ..one }));
+
+    let files = [
+        archive::NewEntry { path: "a".into(), mode: 0o644, mtime: 0, data: &payload },
+        archive::NewEntry { path: "empty".into(), mode: 0o644, mtime: 0, data: &[] },
+    ];
+    let ar = archive::create(&files, &ChainOptions::default()).unwrap();
+    assert_eq!(verify(&ar, &opts), Ok(Verified { format: "CVPA", ..one }));
+    let empty = archive::create(&files[1..], &ChainOptions::default()).unwrap();
+    assert_eq!(verify(&empty, &opts).unwrap().canvases, 0);
+
+    let err = verify(b"nope", &opts).unwrap_err();
+    assert_eq!((err.stage, err.canvas), (Stage::Parse, None));
+}
+
+#[test]
+fn reports_the_first_failing_stage() {
+    let payload = common::pattern(9_000, 37, 251);
+    let mut raw = encode_with(&payload, &EncodeOptions::default()).unwrap();
+    nudge_rg(&mut raw);
+    let full = canvapress::RG_LIMIT_EXACT;
+    let err = verify(&raw, &DecodeOptions::default()).unwrap_err();
+    let not_full = CvpError::RgNotFull { lane: 0, pidx: 0, value: full - 1 };
+    assert_eq!(err, VerifyError { stage: Stage::Convergence, canvas: None, error: not_full });
+    let err = verify(&raw, &DecodeOptions { cross_check: true, ..Default::default() }).unwrap_err();
+    assert_eq!(err.stage, Stage::CrossCheck);
+    assert_eq!(err.to_string().split(':').next(), Some("cross-check failed"));
+
+    let mut chained = chain::encode_with(&payload, &ChainOptions { max_steps: 4_000, ..Default::default() }).unwrap();
+    let link = chain::links(&chained).unwrap()[1];
+    let (offset, len) = (link.offset, link.raw.len());
+    nudge_rg(&mut chained[offset..offset + len]);
+    let err = verify(&chained, &DecodeOptions::default()).unwrap_err();
+    assert_eq!((err.stage, err.canvas), (Stage::Convergence, Some(1)));
+    assert!(err.to_string().starts_with("convergence failed in canvas 1: "));
+
+    // an edited trailer no longer matches the chain digest
+    let end = offset + len;
+    chained[end - 1] ^= 1;
+    let err = verify(&chained, &DecodeOptions::default()).unwrap_err();
+    assert_eq!(err, VerifyError { stage: Stage::Parse, canvas: None, error: CvpError::ChainDigestMismatch });
+}
@@ -0,0 +1,50 @@
+use canvapress::{
+    cvp2_to_cvp1, decode_fill, decode_view, encode_with, unpack, CanvasView, CvpError, DecodeOptions,
+    EncodeOptions, PIXELS,
+};
+
+mod common;
+
+#[test]
+fn view_matches_unpack() {
+    let payload = common::pattern(5_000, 37, 256);
+    let cvp2 = encode_with(&payload, &EncodeOptions::default()).unwrap();
+    let compact = encode_with(&payload, &EncodeOptions { rg_elided: true, a_varint: true, ..Default::default() }).unwrap();
+    let cvp1 = cvp2_to_cvp1(&cvp2).unwrap();
+
+    for raw in [&cvp2, &compact, &cvp1] {
+        let c = unpack(raw).unwrap();
+        let view = CanvasView::new(raw).unwrap();
+        assert_eq!(view.header(), &c.header);
+        assert_eq!(view.n(), payload.len() as u64);
+        assert_eq!(view.integrity(), c.integrity);
+
+        let mut entries = 0;
+        for entry in view.a_entries() {
+            let pages: Vec<(u64, u64)> = entry.pages.collect();
+            assert_eq!(pages.len(), c.a.db[&entry.pidx].len());
+            for (page, mask) in pages {
+                assert_eq!(c.a.db[&entry.pidx][&page], mask);
+            }
+            entries += 1;
+        }
+        assert_eq!(entries, c.a.db.len());
+
+        for pidx in [0, 5, 517, 1000, PIXELS as u32 - 1] {
+            assert_eq!(view.r(pidx).unwrap(), c.rg.r[pidx as usize]);
+            assert_eq!(view.g(pidx).unwrap(), c.rg.g[pidx as usize]);
+        }
+        assert_eq!(view.r(PIXELS as u32).unwrap_err(), CvpError::PidxOutOfRange { pidx: PIXELS as u32 });
+
+        assert!(decode_view(&view, &DecodeOptions::default()).unwrap() == payload);
+    }
+}
+
+#[test]
+fn view_rejects_what_unpack_rejects() {
+    let mut raw = encode_with(b"view", &EncodeOptions::default()).unwrap();
+    raw.push(0);
+    assert_eq!(CanvasView::new(&raw).unwrap_err(), unpack(&raw).unwrap_err());
+    assert!(decode_fill(&raw).is_err());
+    assert_eq!(CanvasView::new(b"CV").unwrap_err(), CvpError::TooSmall { len: 2 });
+}
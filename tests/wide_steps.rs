@@ -0,0 +1,55 @@
+use canvapress::cvp2::{self, FLAG_A_VARINT};
+use canvapress::{
+    build_step_index_from_a, build_step_index_from_a64, encode_with, lane_k, raw_unpack, unpack, ABitset, CanvasConfig,
+    CvpError, EncodeOptions, PIXELS,
+};
+
+#[test]
+fn steps_beyond_u32_in_bitset_and_lanes() {
+    let big = 1u64 << 40;
+    let mut a = ABitset::new();
+    a.set_step(3, big);
+    assert_eq!(a.db[&3][&(big >> 6)], 1);
+    a.clear_step(3, big).unwrap();
+    assert!(a.is_empty());
+
+    assert_eq!(lane_k(u64::MAX), (0, 1 << 63));
+    assert_eq!(lane_k(big), (1, big / 2));
+}
+
+#[test]
+fn step_index_variants_agree() {
+    let raw = encode_with(b"sixty-four bit step index", &EncodeOptions::default()).unwrap();
+    let (rg, a, n) = raw_unpack(&raw).unwrap();
+    let wide = build_step_index_from_a64(&a, n, rg.profile.cfg).unwrap();
+    assert_eq!(build_step_index_from_a(&a, n as u32).unwrap(), wide);
+
+    // pidx is checked against the canvas the index is built for
+    let mut off = ABitset::new();
+    off.set_step(PIXELS as u32, 1);
+    let out_of_range = CvpError::PidxOutOfRange { pidx: PIXELS as u32 };
+    assert_eq!(build_step_index_from_a(&off, 1).unwrap_err(), out_of_range);
+    let tall = CanvasConfig::new(512, 1024).unwrap();
+    assert_eq!(build_step_index_from_a64(&off, 1, tall).unwrap(), [0, PIXELS as u32]);
+}
+
+#[test]
+fn format_limits_are_errors_not_truncation() {
+    let mut c = unpack(&encode_with(b"x", &EncodeOptions::default()).unwrap()).unwrap();
+    let big = 1u64 << 40;
+    c.a.set_step(7, big);
+    c.header.n = big;
+
+    // plain CVP2 A stores u32 pages
+    assert_eq!(
+        cvp2::pack(&c).unwrap_err(),
+        CvpError::FormatLimit { format: "CVP2 plain A", field: "page", value: big >> 6, max: u32::MAX as u64 }
+    );
+    c.header.flags |= FLAG_A_VARINT;
+    assert!(cvp2::pack(&c).is_ok());
+
+    // a CVP2 header may carry N > u32::MAX; this one fails only because A is short
+    let mut raw = encode_with(b"x", &EncodeOptions::default()).unwrap();
+    raw[20..28].copy_from_slice(&(1u64 << 33).to_le_bytes());
+    assert_eq!(raw_unpack(&raw).unwrap_err(), CvpError::StepCountMismatch { bits: 1, n: 1 << 33 });
+}